@@ -4,16 +4,30 @@ use rocket::{
     Request, State,
 };
 
-use crate::login_service::LoginService;
+use crate::{
+    config::Config,
+    logging::RequestUser,
+    login_service::{LoginError, LoginService},
+    rate_limit::{Budget, ClientKey, RateLimiter, RetryAfter},
+    request_id::RequestId,
+};
 
 /// Contains auth information from request
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AuthInfo {
     pub user_id: i32,
+    pub is_moderator: bool,
+}
+
+impl AuthInfo {
+    /// Checks if user can manage cache with specified owner
+    pub fn can_manage(&self, owner_id: Option<i32>) -> bool {
+        self.is_moderator || owner_id == Some(self.user_id)
+    }
 }
 
 /// All errors getting auth info
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum AuthError {
     /// Failed to find required Authetication header
     NoHeader,
@@ -25,6 +39,41 @@ pub enum AuthError {
     HeaderFormatInvalid,
     /// Invalid credentials
     InvalidCredentials,
+    /// Too many failed attempts from client
+    TooManyFailures,
+    /// Credentials could not be checked
+    LoginUnavailable,
+}
+
+/// Auth failure of current request, kept for error catchers
+struct AuthFailure(Option<AuthError>);
+
+impl AuthError {
+    /// Returns error of auth guard if it failed during request
+    pub fn of(req: &Request<'_>) -> Option<AuthError> {
+        req.local_cache(|| AuthFailure(None)).0
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            AuthError::NoHeader => "Authorization header required",
+            AuthError::NotSupportedAuth => "Only Basic authorization is supported",
+            AuthError::BadCount => "Only one Authorization header allowed",
+            AuthError::HeaderFormatInvalid => "Invalid Authorization header format",
+            AuthError::InvalidCredentials => "Invalid credentials",
+            AuthError::TooManyFailures => "Too many failed authentication attempts",
+            AuthError::LoginUnavailable => "Login service is unavailable, try again later",
+        }
+    }
+}
+
+type AuthResult = Result<AuthInfo, (Status, AuthError)>;
+
+/// Remembers failure and spends auth failures budget of client
+fn fail(req: &Request<'_>, limiter: &RateLimiter, status: Status, error: AuthError) -> AuthResult {
+    req.local_cache(|| AuthFailure(Some(error)));
+    let _ = limiter.take(Budget::AuthFailure, ClientKey::ip_of(req));
+    Err((status, error))
 }
 
 #[rocket::async_trait]
@@ -32,52 +81,122 @@ impl<'r> FromRequest<'r> for AuthInfo {
     type Error = AuthError;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let auths_headers: Vec<_> = req.headers().get("Authorization").collect();
-
-        // Parsing
-        if auths_headers.is_empty() {
-            return Outcome::Failure((Status::Unauthorized, AuthError::NoHeader));
+        // Guard may be requested several times, login service is called once
+        match req.local_cache_async(authenticate(req)).await {
+            Ok(info) => Outcome::Success(info.clone()),
+            Err((status, error)) => Outcome::Error((*status, *error)),
         }
+    }
+}
 
-        if 1 < auths_headers.len() {
-            return Outcome::Failure((Status::BadRequest, AuthError::BadCount));
-        }
+async fn authenticate(req: &Request<'_>) -> AuthResult {
+    let limiter: &State<RateLimiter> = req
+        .guard()
+        .await
+        .expect("Rate limiter must be added to rocket");
 
-        let mut auth_str = auths_headers[0].split_whitespace();
-        let Some(scheme) = auth_str.next() else {
-            return Outcome::Failure((Status::BadRequest, AuthError::HeaderFormatInvalid));
-        };
+    if let Err(after) = limiter.check(Budget::AuthFailure, ClientKey::ip_of(req)) {
+        RetryAfter::set(req, after);
+        req.local_cache(|| AuthFailure(Some(AuthError::TooManyFailures)));
+        return Err((Status::TooManyRequests, AuthError::TooManyFailures));
+    }
 
-        if scheme != "Basic" {
-            return Outcome::Failure((Status::NotImplemented, AuthError::NotSupportedAuth));
-        }
+    let auths_headers: Vec<_> = req.headers().get("Authorization").collect();
 
-        let Some(auth_data) = auth_str.next() else {
-            return Outcome::Failure((Status::BadRequest, AuthError::HeaderFormatInvalid));
-        };
-
-        // Decode base64
-        let Ok(credentials_raw) = base64::decode(auth_data) else {
-            return Outcome::Failure((Status::BadRequest, AuthError::HeaderFormatInvalid));
-        };
-
-        let Ok(credentials) = String::from_utf8(credentials_raw) else {
-            return Outcome::Failure((Status::BadRequest, AuthError::HeaderFormatInvalid));
-        };
-
-        let Some((email, password)) = credentials.split_once(':') else {
-            return Outcome::Failure((Status::BadRequest, AuthError::HeaderFormatInvalid));
-        };
-
-        // Request from login_service correctness and get user id
-        let login_service: &State<LoginService> = req
-            .guard()
-            .await
-            .expect("Login service must be added to rocket");
-        let Some(user_id) = login_service.login(email, password).await else {
-            return Outcome::Failure((Status::Unauthorized, AuthError::InvalidCredentials));
-        };
-
-        Outcome::Success(AuthInfo { user_id })
+    // Parsing
+    if auths_headers.is_empty() {
+        return fail(req, limiter, Status::Unauthorized, AuthError::NoHeader);
     }
+
+    if 1 < auths_headers.len() {
+        return fail(req, limiter, Status::BadRequest, AuthError::BadCount);
+    }
+
+    let mut auth_str = auths_headers[0].split_whitespace();
+    let Some(scheme) = auth_str.next() else {
+        return fail(
+            req,
+            limiter,
+            Status::BadRequest,
+            AuthError::HeaderFormatInvalid,
+        );
+    };
+
+    if scheme != "Basic" {
+        return fail(
+            req,
+            limiter,
+            Status::NotImplemented,
+            AuthError::NotSupportedAuth,
+        );
+    }
+
+    let Some(auth_data) = auth_str.next() else {
+        return fail(
+            req,
+            limiter,
+            Status::BadRequest,
+            AuthError::HeaderFormatInvalid,
+        );
+    };
+
+    // Decode base64
+    let Ok(credentials_raw) = base64::decode(auth_data) else {
+        return fail(
+            req,
+            limiter,
+            Status::BadRequest,
+            AuthError::HeaderFormatInvalid,
+        );
+    };
+
+    let Ok(credentials) = String::from_utf8(credentials_raw) else {
+        return fail(
+            req,
+            limiter,
+            Status::BadRequest,
+            AuthError::HeaderFormatInvalid,
+        );
+    };
+
+    let Some((email, password)) = credentials.split_once(':') else {
+        return fail(
+            req,
+            limiter,
+            Status::BadRequest,
+            AuthError::HeaderFormatInvalid,
+        );
+    };
+
+    // Request from login_service correctness and get user id
+    let login_service: &State<LoginService> = req
+        .guard()
+        .await
+        .expect("Login service must be added to rocket");
+    let request_id = RequestId::of(req);
+    let user_id = match login_service.login(email, password, request_id).await {
+        Ok(user_id) => user_id,
+        Err(LoginError::Rejected) => {
+            return fail(
+                req,
+                limiter,
+                Status::Unauthorized,
+                AuthError::InvalidCredentials,
+            )
+        }
+        // Outage is not client's fault, so it does not spend failures budget
+        Err(LoginError::Unavailable) => {
+            req.local_cache(|| AuthFailure(Some(AuthError::LoginUnavailable)));
+            return Err((Status::ServiceUnavailable, AuthError::LoginUnavailable));
+        }
+    };
+
+    let config: &State<Config> = req.guard().await.expect("Config must be added to rocket");
+
+    RequestUser::set(req, user_id);
+
+    Ok(AuthInfo {
+        user_id,
+        is_moderator: config.moderator_ids.contains(&user_id),
+    })
 }
@@ -0,0 +1,199 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    process::exit,
+};
+
+use clap::{Args, Parser, Subcommand};
+use mongodb::bson::{oid::ObjectId, Bson};
+use msd_cache_service::{
+    config::{Config, DatabaseConfig},
+    db::{self, parse_bbox, AdminDatabase, CacheFilter, LatLong},
+};
+use rocket::futures::TryStreamExt;
+
+/// Maintenance of caches stored in MongoDB. Reads `DATABASE_URL` and
+/// `DATABASE_NAME` like the service does
+#[derive(Parser)]
+#[command(name = "msd-cache-admin")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List caches matching filter
+    List(ListArgs),
+    /// Change owner of one cache or of all caches of a user
+    Reassign {
+        /// New owner id
+        #[arg(long)]
+        to: i32,
+        /// Cache id
+        #[arg(long, conflicts_with = "from", required_unless_present = "from")]
+        cache: Option<String>,
+        /// Current owner id
+        #[arg(long)]
+        from: Option<i32>,
+    },
+    /// Soft delete all caches of a user
+    Archive {
+        #[arg(long)]
+        owner: i32,
+    },
+    /// Write all cache documents as JSON Lines
+    Export {
+        /// Output file, standard output by default
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Insert or replace caches from JSON Lines written by `export`
+    Import {
+        /// Input file, standard input by default
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Print collection statistics
+    Stats,
+}
+
+#[derive(Args)]
+struct ListArgs {
+    #[arg(long)]
+    owner: Option<i32>,
+    /// Bounds as `min_lat,min_lng,max_lat,max_lng`
+    #[arg(long, value_parser = parse_bbox)]
+    bbox: Option<(LatLong, LatLong)>,
+    /// Text contained in description or hint
+    #[arg(long)]
+    search: Option<String>,
+    /// Include soft deleted caches
+    #[arg(long)]
+    deleted: bool,
+    #[arg(long)]
+    limit: Option<i64>,
+}
+
+#[rocket::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    dotenvy::dotenv().ok();
+    let config = match DatabaseConfig::load(&Config::figment()) {
+        Ok(config) => config,
+        Err(err) => fail(err),
+    };
+    let admin = AdminDatabase::new(db::mongo_database(&config).await);
+
+    if let Err(err) = run(&admin, cli.command).await {
+        fail(err);
+    }
+}
+
+fn fail(err: impl std::fmt::Display) -> ! {
+    eprintln!("{}", err);
+    exit(1);
+}
+
+async fn run(admin: &AdminDatabase, command: Command) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::List(args) => {
+            let filter = CacheFilter {
+                owner_id: args.owner,
+                bounds: args.bbox,
+                text: args.search,
+                include_deleted: args.deleted,
+                limit: args.limit,
+            };
+
+            let mut out = BufWriter::new(io::stdout());
+            for cache in admin.find_caches(&filter).await? {
+                writeln!(
+                    out,
+                    "{}\t{}\towner={}\t{},{}\t{}{}",
+                    cache.id.map(|id| id.to_hex()).unwrap_or_default(),
+                    cache.code.as_deref().unwrap_or("-"),
+                    cache
+                        .owner_id
+                        .map(|id| id.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    cache.position.lat,
+                    cache.position.lng,
+                    cache.name,
+                    cache
+                        .deleted_at
+                        .map(|at| format!("\tdeleted={}", at))
+                        .unwrap_or_default(),
+                )?;
+            }
+        }
+        Command::Reassign { to, cache, from } => {
+            if let Some(id) = cache {
+                let id = ObjectId::parse_str(&id)?;
+                if !admin.reassign_cache(id, to).await? {
+                    return Err(format!("cache {} not found", id).into());
+                }
+                println!("Cache {} reassigned to {}", id, to);
+            } else if let Some(from) = from {
+                let count = admin.reassign_owner(from, to).await?;
+                println!("{} caches reassigned from {} to {}", count, from, to);
+            }
+        }
+        Command::Archive { owner } => {
+            let count = admin.archive_owner(owner).await?;
+            println!("{} caches of {} archived", count, owner);
+        }
+        Command::Export { file } => {
+            let out: Box<dyn Write + Send> = match file {
+                Some(path) => Box::new(File::create(path)?),
+                None => Box::new(io::stdout()),
+            };
+            let mut out = BufWriter::new(out);
+
+            let mut documents = admin.export().await?;
+            while let Some(document) = documents.try_next().await? {
+                let json = Bson::Document(document).into_relaxed_extjson();
+                writeln!(out, "{}", json)?;
+            }
+            out.flush()?;
+        }
+        Command::Import { file } => {
+            let input: Box<dyn BufRead + Send> = match file {
+                Some(path) => Box::new(BufReader::new(File::open(path)?)),
+                None => Box::new(BufReader::new(io::stdin())),
+            };
+
+            let mut count = 0;
+            for (number, line) in input.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let json: serde_json::Value = serde_json::from_str(&line)
+                    .map_err(|err| format!("line {}: {}", number + 1, err))?;
+                let Ok(Bson::Document(document)) = Bson::try_from(json) else {
+                    return Err(format!("line {}: expected JSON object", number + 1).into());
+                };
+
+                admin.import(document).await?;
+                count += 1;
+            }
+            println!("{} caches imported", count);
+        }
+        Command::Stats => {
+            let stats = admin.stats().await?;
+            println!("caches:        {}", stats.caches);
+            println!("active:        {}", stats.caches - stats.deleted);
+            println!("deleted:       {}", stats.deleted);
+            println!("owners:        {}", stats.owners);
+            println!("revisions:     {}", stats.revisions);
+            println!("audit entries: {}", stats.audit_entries);
+            println!("data size:     {} bytes", stats.data_size);
+        }
+    }
+
+    Ok(())
+}
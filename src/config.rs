@@ -0,0 +1,374 @@
+use std::{collections::HashSet, fmt, str::FromStr, time::Duration};
+
+use rocket::{
+    figment::{providers::Env, Figment},
+    http::Method,
+};
+use serde::{Deserialize, Deserializer};
+
+/// Settings read without `ROCKET_` prefix from environment
+const ENV_KEYS: &[&str] = &[
+    "storage_backend",
+    "database_url",
+    "database_name",
+    "run_migrations",
+    "login_service_address",
+    "moderator_ids",
+    "deleted_retention_days",
+    "cors_allowed_origins",
+    "cors_allowed_methods",
+    "cors_allowed_headers",
+    "cors_max_age",
+    "rate_limit_reads_per_minute",
+    "rate_limit_writes_per_minute",
+    "rate_limit_auth_failures_per_minute",
+    "trusted_ip_header",
+    "idempotency_ttl_hours",
+];
+
+/// Service configuration. Read from `Rocket.toml`, `ROCKET_*` variables
+/// and plain environment variables listed in `ENV_KEYS`
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    /// Where caches are stored
+    #[serde(default)]
+    pub storage_backend: StorageBackend,
+    /// MongoDB connection string. Required for `mongodb` storage only
+    #[serde(default)]
+    pub database_url: String,
+    #[serde(default)]
+    pub database_name: String,
+    /// Apply pending data migrations on launch
+    #[serde(default = "default_run_migrations")]
+    pub run_migrations: bool,
+    /// Base URL of login service, with or without trailing slash
+    pub login_service_address: String,
+
+    /// Users allowed to manage caches of other users
+    #[serde(default, deserialize_with = "list")]
+    pub moderator_ids: HashSet<i32>,
+
+    /// Days to keep soft deleted caches before purge
+    #[serde(default = "default_deleted_retention_days")]
+    pub deleted_retention_days: u64,
+
+    /// Origins allowed to make cross-origin requests. `*` allows any origin
+    /// but then credentials are not allowed by browsers
+    #[serde(default = "default_cors_allowed_origins", deserialize_with = "list")]
+    pub cors_allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_allowed_methods", deserialize_with = "list")]
+    pub cors_allowed_methods: Vec<String>,
+    #[serde(default = "default_cors_allowed_headers", deserialize_with = "list")]
+    pub cors_allowed_headers: Vec<String>,
+    /// Seconds browsers may cache preflight results
+    #[serde(default = "default_cors_max_age")]
+    pub cors_max_age: u32,
+
+    /// Read requests allowed per user or IP each minute
+    #[serde(default = "default_rate_limit_reads_per_minute")]
+    pub rate_limit_reads_per_minute: u32,
+    /// Write requests allowed per user each minute
+    #[serde(default = "default_rate_limit_writes_per_minute")]
+    pub rate_limit_writes_per_minute: u32,
+    /// Failed authentications allowed per IP each minute
+    #[serde(default = "default_rate_limit_auth_failures_per_minute")]
+    pub rate_limit_auth_failures_per_minute: u32,
+    /// Header with client IP set by trusted reverse proxy, e.g. `X-Real-IP`.
+    /// Without it limits are keyed by address of connected peer, as clients
+    /// could spoof the header otherwise
+    #[serde(default)]
+    pub trusted_ip_header: Option<String>,
+
+    /// Hours responses of requests with `Idempotency-Key` are replayed
+    #[serde(default = "default_idempotency_ttl_hours")]
+    pub idempotency_ttl_hours: u64,
+}
+
+/// Storage implementation used for caches and audit log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Mongodb,
+    /// Process memory, data is lost on restart. For tests and local demos
+    Memory,
+}
+
+fn default_run_migrations() -> bool {
+    true
+}
+
+fn default_deleted_retention_days() -> u64 {
+    30
+}
+
+fn default_cors_allowed_origins() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+fn default_cors_allowed_methods() -> Vec<String> {
+    ["GET", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"]
+        .map(str::to_string)
+        .to_vec()
+}
+
+fn default_cors_allowed_headers() -> Vec<String> {
+    [
+        "Authorization",
+        "Content-Type",
+        "X-Request-Id",
+        "Idempotency-Key",
+    ]
+    .map(str::to_string)
+    .to_vec()
+}
+
+fn default_cors_max_age() -> u32 {
+    24 * 60 * 60
+}
+
+fn default_rate_limit_reads_per_minute() -> u32 {
+    120
+}
+
+fn default_rate_limit_writes_per_minute() -> u32 {
+    30
+}
+
+fn default_rate_limit_auth_failures_per_minute() -> u32 {
+    10
+}
+
+fn default_idempotency_ttl_hours() -> u64 {
+    24
+}
+
+/// Accepts list or comma separated string, e.g. `MODERATOR_IDS=1,2`
+fn list<'de, D, T, C>(deserializer: D) -> Result<C, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+    C: FromIterator<T>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Items<T> {
+        List(Vec<T>),
+        Text(String),
+        Single(T),
+    }
+
+    match Items::deserialize(deserializer)? {
+        Items::List(items) => Ok(items.into_iter().collect()),
+        Items::Text(items) => items
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| item.parse().map_err(serde::de::Error::custom))
+            .collect(),
+        Items::Single(item) => Ok(std::iter::once(item).collect()),
+    }
+}
+
+/// Reasons configuration cannot be used
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Missing setting or setting of wrong type
+    Extract(Box<rocket::figment::Error>),
+    /// Settings with invalid values
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Extract(err) => write!(f, "Invalid configuration: {}", err),
+            ConfigError::Invalid(errors) => {
+                write!(f, "Invalid configuration: {}", errors.join("; "))
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Returns rocket figment extended with service settings
+    pub fn figment() -> Figment {
+        rocket::Config::figment().merge(Env::raw().only(ENV_KEYS))
+    }
+
+    /// Reads and validates configuration
+    pub fn load(figment: &Figment) -> Result<Self, ConfigError> {
+        let config: Config = figment
+            .extract()
+            .map_err(|err| ConfigError::Extract(Box::new(err)))?;
+
+        let errors = config.validate();
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+
+        Ok(config)
+    }
+
+    /// Returns description of all invalid settings
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.storage_backend == StorageBackend::Mongodb {
+            errors.extend(self.database().validate());
+        }
+
+        match reqwest::Url::parse(&self.login_service_address) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+            _ => errors.push(format!(
+                "login_service_address '{}' must be an http(s) URL",
+                self.login_service_address
+            )),
+        }
+
+        if self.deleted_retention_days == 0 {
+            errors.push("deleted_retention_days must be at least 1".to_string());
+        }
+
+        for origin in &self.cors_allowed_origins {
+            if origin == "*" {
+                continue;
+            }
+
+            // Origin is scheme, host and optional port without path
+            match reqwest::Url::parse(origin) {
+                Ok(url)
+                    if (url.scheme() == "http" || url.scheme() == "https")
+                        && url.path() == "/"
+                        && !origin.ends_with('/') => {}
+                _ => errors.push(format!(
+                    "cors_allowed_origins entry '{}' must be '*' or an origin like 'https://example.com'",
+                    origin
+                )),
+            }
+        }
+
+        for method in &self.cors_allowed_methods {
+            if Method::from_str(method).is_err() {
+                errors.push(format!(
+                    "cors_allowed_methods entry '{}' is not an HTTP method",
+                    method
+                ));
+            }
+        }
+
+        if let Some(header) = self.ip_header() {
+            if !header
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                errors.push(format!(
+                    "trusted_ip_header '{}' is not a valid header name",
+                    header
+                ));
+            }
+        }
+
+        if self.idempotency_ttl_hours == 0 {
+            errors.push("idempotency_ttl_hours must be at least 1".to_string());
+        }
+
+        for (name, value) in [
+            (
+                "rate_limit_reads_per_minute",
+                self.rate_limit_reads_per_minute,
+            ),
+            (
+                "rate_limit_writes_per_minute",
+                self.rate_limit_writes_per_minute,
+            ),
+            (
+                "rate_limit_auth_failures_per_minute",
+                self.rate_limit_auth_failures_per_minute,
+            ),
+        ] {
+            if value == 0 {
+                errors.push(format!("{} must be at least 1", name));
+            }
+        }
+
+        errors
+    }
+
+    /// MongoDB settings of the service
+    pub fn database(&self) -> DatabaseConfig {
+        DatabaseConfig {
+            database_url: self.database_url.clone(),
+            database_name: self.database_name.clone(),
+        }
+    }
+
+    /// Base URL of login service. Valid after `load`
+    pub fn login_service_url(&self) -> reqwest::Url {
+        reqwest::Url::parse(&self.login_service_address)
+            .expect("login_service_address is validated on load")
+    }
+
+    /// Header with client IP if it is trusted
+    pub fn ip_header(&self) -> Option<&str> {
+        self.trusted_ip_header
+            .as_deref()
+            .map(str::trim)
+            .filter(|header| !header.is_empty())
+    }
+
+    pub fn deleted_retention(&self) -> Duration {
+        Duration::from_secs(self.deleted_retention_days * 24 * 60 * 60)
+    }
+
+    pub fn idempotency_ttl(&self) -> Duration {
+        Duration::from_secs(self.idempotency_ttl_hours * 60 * 60)
+    }
+}
+
+/// MongoDB settings only, for tools which do not serve requests
+#[derive(Debug, Clone, Deserialize)]
+pub struct DatabaseConfig {
+    /// MongoDB connection string
+    pub database_url: String,
+    pub database_name: String,
+}
+
+impl DatabaseConfig {
+    /// Reads and validates database settings from the same sources as `Config`
+    pub fn load(figment: &Figment) -> Result<Self, ConfigError> {
+        let config: DatabaseConfig = figment
+            .extract()
+            .map_err(|err| ConfigError::Extract(Box::new(err)))?;
+
+        let errors = config.validate();
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if !self.database_url.starts_with("mongodb://")
+            && !self.database_url.starts_with("mongodb+srv://")
+        {
+            errors
+                .push("database_url must start with 'mongodb://' or 'mongodb+srv://'".to_string());
+        }
+
+        if self.database_name.is_empty()
+            || self.database_name.contains(['/', '\\', '.', ' ', '"', '$'])
+        {
+            errors.push(format!(
+                "database_name '{}' is not a valid MongoDB database name",
+                self.database_name
+            ));
+        }
+
+        errors
+    }
+}
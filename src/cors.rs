@@ -1,21 +1,50 @@
 use rocket::fairing::{Fairing, Info, Kind};
 use rocket::http::{Header, Method, Status};
+use rocket::request::{FromRequest, Outcome};
 use rocket::{Build, Request, Response, Rocket};
 
+use crate::config::Config;
+
 pub trait RocketCorsEnabler {
-    fn enable_cors(self) -> Self;
+    fn enable_cors(self, config: &Config) -> Self;
 }
 
 impl RocketCorsEnabler for Rocket<Build> {
-    fn enable_cors(self) -> Self {
-        self.attach(CORS)
+    fn enable_cors(self, config: &Config) -> Self {
+        self.attach(Cors::new(config))
+            .mount("/", routes![preflight])
     }
 }
 
-pub struct CORS;
+/// Injects CORS headers for allowed origins
+pub struct Cors {
+    allowed_origins: Vec<String>,
+    allowed_methods: String,
+    allowed_headers: String,
+    max_age: String,
+}
+
+impl Cors {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            allowed_origins: config.cors_allowed_origins.clone(),
+            allowed_methods: config.cors_allowed_methods.join(", "),
+            allowed_headers: config.cors_allowed_headers.join(", "),
+            max_age: config.cors_max_age.to_string(),
+        }
+    }
+
+    fn any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|o| o == "*")
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.any_origin() || self.allowed_origins.iter().any(|o| o == origin)
+    }
+}
 
 #[async_trait]
-impl Fairing for CORS {
+impl Fairing for Cors {
     fn info(&self) -> Info {
         Info {
             name: "CORS headers injector",
@@ -24,19 +53,92 @@ impl Fairing for CORS {
     }
 
     async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
-        // CORS headers
-        // TODO! Change Access-Control-Allow-Origin
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
-        response.set_header(Header::new(
-            "Access-Control-Allow-Methods",
-            "POST, GET, PATCH, OPTIONS",
-        ));
-        response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
-        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
-
-        // Preflight request handling
+        if !self.any_origin() {
+            // Response depends on origin, so caches must not share it between origins
+            response.adjoin_header(Header::new("Vary", "Origin"));
+        }
+
+        let Some(origin) = request.headers().get_one("Origin") else {
+            return;
+        };
+
+        if !self.allows(origin) {
+            return;
+        }
+
+        if self.any_origin() {
+            response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+        } else {
+            response.set_header(Header::new(
+                "Access-Control-Allow-Origin",
+                origin.to_string(),
+            ));
+            response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        }
+
         if request.method() == Method::Options {
-            response.set_status(Status::NoContent)
+            response.set_header(Header::new(
+                "Access-Control-Allow-Methods",
+                self.allowed_methods.clone(),
+            ));
+            response.set_header(Header::new(
+                "Access-Control-Allow-Headers",
+                self.allowed_headers.clone(),
+            ));
+            response.set_header(Header::new("Access-Control-Max-Age", self.max_age.clone()));
         }
     }
 }
+
+/// Preflight request for URL served by some route
+pub struct Preflight;
+
+/// Checks if route path template like `/api/v1/cache/<id>` matches request path segments
+fn path_matches(route_path: &str, segments: &[&str]) -> bool {
+    let route_segments: Vec<_> = route_path.split('/').filter(|s| !s.is_empty()).collect();
+
+    for (i, route_segment) in route_segments.iter().enumerate() {
+        let dynamic = route_segment.starts_with('<') && route_segment.ends_with('>');
+        if dynamic && route_segment.ends_with("..>") {
+            return true;
+        }
+
+        match segments.get(i) {
+            Some(segment) if dynamic || segment == route_segment => {}
+            _ => return false,
+        }
+    }
+
+    route_segments.len() == segments.len()
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Preflight {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // Any method is fine for simple OPTIONS request
+        let requested_method = req
+            .headers()
+            .get_one("Access-Control-Request-Method")
+            .and_then(|m| m.parse::<Method>().ok());
+
+        let segments: Vec<_> = req.uri().path().segments().collect();
+        let served = req.rocket().routes().any(|route| {
+            route.method != Method::Options
+                && requested_method.is_none_or(|m| m == route.method)
+                && path_matches(route.uri.path(), &segments)
+        });
+
+        if served {
+            Outcome::Success(Preflight)
+        } else {
+            Outcome::Forward(Status::NotFound)
+        }
+    }
+}
+
+#[options("/<_..>")]
+pub fn preflight(_preflight: Preflight) -> Status {
+    Status::NoContent
+}
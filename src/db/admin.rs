@@ -0,0 +1,307 @@
+use mongodb::{
+    bson::{doc, from_document, oid::ObjectId, Bson, DateTime, Document, Regex},
+    error::Error,
+    options::{FindOneAndUpdateOptions, FindOptions, ReplaceOptions, ReturnDocument},
+    Collection, Database,
+};
+use rocket::futures::{Stream, TryStreamExt};
+
+use super::{
+    cache::Departure, mongo::next_code, AuditAction, AuditEntry, Cache, LatLong, OPERATOR_ACTOR_ID,
+};
+use crate::request_id::RequestId;
+
+/// Filter of caches listed by operators
+#[derive(Debug, Default)]
+pub struct CacheFilter {
+    pub owner_id: Option<i32>,
+    /// South-west and north-east corners
+    pub bounds: Option<(LatLong, LatLong)>,
+    /// Case insensitive text contained in description or hint
+    pub text: Option<String>,
+    /// Include soft deleted caches
+    pub include_deleted: bool,
+    pub limit: Option<i64>,
+}
+
+impl CacheFilter {
+    fn to_document(&self) -> Document {
+        let mut filter = Document::new();
+        if !self.include_deleted {
+            filter.insert("deleted_at", Bson::Null);
+        }
+        if let Some(owner_id) = self.owner_id {
+            filter.insert("owner_id", owner_id);
+        }
+        if let Some((sw, ne)) = &self.bounds {
+            filter.insert("position.lat", doc! { "$gte": sw.lat, "$lte": ne.lat });
+            filter.insert("position.lng", doc! { "$gte": sw.lng, "$lte": ne.lng });
+        }
+        if let Some(text) = &self.text {
+            let pattern = Regex {
+                pattern: escape_regex(text),
+                options: "i".to_string(),
+            };
+            filter.insert(
+                "$or",
+                vec![
+                    doc! { "description": pattern.clone() },
+                    doc! { "hint": pattern },
+                ],
+            );
+        }
+        filter
+    }
+}
+
+fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Collection statistics
+#[derive(Debug)]
+pub struct CacheStats {
+    pub caches: u64,
+    pub deleted: u64,
+    pub owners: u64,
+    pub revisions: u64,
+    pub audit_entries: u64,
+    /// Uncompressed size of cache documents in bytes
+    pub data_size: i64,
+}
+
+/// Maintenance operations over MongoDB storage used by operators.
+/// Changes update `updated_at`, so delta sync sees them, and are recorded
+/// in the audit log as made by `OPERATOR_ACTOR_ID`
+pub struct AdminDatabase {
+    caches: Collection<Cache>,
+    documents: Collection<Document>,
+    audit: Collection<AuditEntry>,
+    database: Database,
+    /// Id shared by audit entries of one admin session
+    request_id: RequestId,
+}
+
+impl AdminDatabase {
+    pub fn new(database: Database) -> Self {
+        Self {
+            caches: database.collection("cache"),
+            documents: database.collection("cache"),
+            audit: database.collection("audit"),
+            database,
+            request_id: RequestId(format!("msd-cache-admin-{}", ObjectId::new().to_hex())),
+        }
+    }
+
+    async fn record(
+        &self,
+        action: AuditAction,
+        id: ObjectId,
+        before: Option<&Cache>,
+        after: Option<&Cache>,
+    ) -> Result<(), Error> {
+        let entry = AuditEntry::new(
+            OPERATOR_ACTOR_ID,
+            action,
+            id,
+            before,
+            after,
+            &self.request_id,
+        );
+        self.audit.insert_one(entry, None).await.map(|_| ())
+    }
+
+    /// Applies `$set` of `fields` with new `updated_at` to each cache matching
+    /// `filter` and records it in audit log. Returns count of changed caches
+    async fn update_each(
+        &self,
+        filter: Document,
+        mut fields: Document,
+        action: AuditAction,
+    ) -> Result<u64, Error> {
+        let matched: Vec<Cache> = self
+            .caches
+            .find(filter.clone(), None)
+            .await?
+            .try_collect()
+            .await?;
+
+        fields.insert("updated_at", DateTime::now());
+        let update = doc! { "$set": fields };
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let mut changed = 0;
+        for before in matched {
+            let Some(id) = before.id else {
+                continue;
+            };
+
+            // Cache changed since it was found is updated only if it still matches
+            let mut filter = filter.clone();
+            filter.insert("_id", id);
+            let Some(after) = self
+                .caches
+                .find_one_and_update(filter, update.clone(), options.clone())
+                .await?
+            else {
+                continue;
+            };
+
+            self.record(action, id, Some(&before), Some(&after)).await?;
+            changed += 1;
+        }
+        Ok(changed)
+    }
+
+    pub async fn find_caches(&self, filter: &CacheFilter) -> Result<Vec<Cache>, Error> {
+        let options = FindOptions::builder()
+            .sort(doc! { "_id": 1 })
+            .limit(filter.limit)
+            .build();
+        let cursor = self.caches.find(filter.to_document(), options).await?;
+        cursor.try_collect().await
+    }
+
+    /// Makes `new_owner` owner of single cache. Returns if cache was found
+    pub async fn reassign_cache(&self, id: ObjectId, new_owner: i32) -> Result<bool, Error> {
+        let changed = self
+            .update_each(
+                doc! { "_id": id },
+                doc! { "owner_id": new_owner },
+                AuditAction::Edit,
+            )
+            .await?;
+        Ok(changed > 0)
+    }
+
+    /// Moves all caches of `owner` to `new_owner`. Returns count of moved caches
+    pub async fn reassign_owner(&self, owner: i32, new_owner: i32) -> Result<u64, Error> {
+        self.update_each(
+            doc! { "owner_id": owner },
+            doc! { "owner_id": new_owner },
+            AuditAction::Edit,
+        )
+        .await
+    }
+
+    /// Soft deletes all caches of owner. They are purged after retention period as usual.
+    /// Returns count of archived caches
+    pub async fn archive_owner(&self, owner: i32) -> Result<u64, Error> {
+        self.update_each(
+            doc! { "owner_id": owner, "deleted_at": null },
+            doc! { "deleted_at": DateTime::now() },
+            AuditAction::Delete,
+        )
+        .await
+    }
+
+    /// All cache documents as stored, including soft deleted ones
+    pub async fn export(&self) -> Result<impl Stream<Item = Result<Document, Error>>, Error> {
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+        self.documents.find(None, options).await
+    }
+
+    /// Inserts document or replaces existing one with the same id.
+    /// Document is marked as changed now, so clients sync it again.
+    /// Document without code keeps code of replaced cache or gets new one
+    pub async fn import(&self, mut document: Document) -> Result<(), Error> {
+        document.insert("updated_at", DateTime::now());
+        let mut imported: Cache = from_document(document.clone())?;
+
+        let previous = match imported.id {
+            Some(id) => self.caches.find_one(doc! { "_id": id }, None).await?,
+            None => None,
+        };
+        if imported.code.as_deref().unwrap_or_default().is_empty() {
+            let code = match previous.as_ref().and_then(|p| p.code.clone()) {
+                Some(code) => code,
+                None => next_code(&self.database).await?,
+            };
+            document.insert("code", &code);
+            imported.code = Some(code);
+        }
+
+        let Some(id) = imported.id else {
+            let inserted = self.documents.insert_one(document, None).await?;
+            if let Some(id) = inserted.inserted_id.as_object_id() {
+                self.record(AuditAction::Create, id, None, Some(&imported))
+                    .await?;
+            }
+            return Ok(());
+        };
+
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.documents
+            .replace_one(doc! { "_id": id }, document, options)
+            .await?;
+        if let Some(departure) = previous.as_ref().and_then(|p| Departure::of(p, &imported)) {
+            self.database
+                .collection::<Departure>("departures")
+                .insert_one(departure, None)
+                .await?;
+        }
+
+        let action = match previous {
+            Some(_) => AuditAction::Edit,
+            None => AuditAction::Create,
+        };
+        self.record(action, id, previous.as_ref(), Some(&imported))
+            .await
+    }
+
+    pub async fn stats(&self) -> Result<CacheStats, Error> {
+        let caches = self.documents.count_documents(None, None).await?;
+        let deleted = self
+            .documents
+            .count_documents(doc! { "deleted_at": { "$ne": null } }, None)
+            .await?;
+        let owners = self.documents.distinct("owner_id", None, None).await?.len() as u64;
+        let revisions = self
+            .database
+            .collection::<Document>("revisions")
+            .count_documents(None, None)
+            .await?;
+        let audit_entries = self
+            .database
+            .collection::<Document>("audit")
+            .count_documents(None, None)
+            .await?;
+
+        // Collection does not exist until first insert
+        let data_size = match self
+            .database
+            .run_command(doc! { "collStats": "cache" }, None)
+            .await
+        {
+            Ok(stats) => stats
+                .get("size")
+                .and_then(|size| match size {
+                    Bson::Int32(size) => Some(*size as i64),
+                    Bson::Int64(size) => Some(*size),
+                    Bson::Double(size) => Some(*size as i64),
+                    _ => None,
+                })
+                .unwrap_or(0),
+            Err(_) if caches == 0 => 0,
+            Err(err) => return Err(err),
+        };
+
+        Ok(CacheStats {
+            caches,
+            deleted,
+            owners,
+            revisions,
+            audit_entries,
+            data_size,
+        })
+    }
+}
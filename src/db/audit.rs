@@ -0,0 +1,85 @@
+use mongodb::bson::{doc, oid::ObjectId, to_document, Bson, DateTime, Document};
+use serde::{Deserialize, Serialize};
+
+use super::Cache;
+use crate::request_id::RequestId;
+
+/// Kind of change made to a cache
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Edit,
+    Delete,
+    /// Cache content replaced by one of its revisions
+    Revert,
+    /// Soft deleted cache returned back
+    Restore,
+}
+
+/// Actor of changes made by operators with `msd-cache-admin`
+pub const OPERATOR_ACTOR_ID: i32 = 0;
+
+/// Single record of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+
+    /// User who made the change, or `OPERATOR_ACTOR_ID`
+    pub actor_id: i32,
+    pub action: AuditAction,
+    pub cache_id: ObjectId,
+    /// Changed fields in form `field: { before, after }`
+    pub changes: Document,
+    pub timestamp: DateTime,
+    pub request_id: String,
+}
+
+impl AuditEntry {
+    pub fn new(
+        actor_id: i32,
+        action: AuditAction,
+        cache_id: ObjectId,
+        before: Option<&Cache>,
+        after: Option<&Cache>,
+        request_id: &RequestId,
+    ) -> Self {
+        Self {
+            id: None,
+            actor_id,
+            action,
+            cache_id,
+            changes: diff(before, after),
+            timestamp: DateTime::now(),
+            request_id: request_id.0.clone(),
+        }
+    }
+}
+
+/// Collects fields which differ between two cache versions. Missing version treated as empty
+fn diff(before: Option<&Cache>, after: Option<&Cache>) -> Document {
+    let as_document = |cache: Option<&Cache>| {
+        cache
+            .map(|c| to_document(c).expect("cache is always serializable"))
+            .unwrap_or_default()
+    };
+    let before = as_document(before);
+    let after = as_document(after);
+
+    let mut changes = Document::new();
+    for key in before.keys().chain(after.keys()) {
+        // Time of change is kept in entry itself
+        if key == "_id" || key == "updated_at" || changes.contains_key(key) {
+            continue;
+        }
+
+        let old = before.get(key).cloned().unwrap_or(Bson::Null);
+        let new = after.get(key).cloned().unwrap_or(Bson::Null);
+        if old != new {
+            changes.insert(key, doc! { "before": old, "after": new });
+        }
+    }
+
+    changes
+}
@@ -1,31 +1,23 @@
-use std::env;
-
-use mongodb::{
-    bson::oid::ObjectId,
-    bson::{doc, Document},
-    error::Error,
-    options::FindOptions,
-    Client, Collection,
-};
-
-use rocket::{
-    futures::TryStreamExt,
-    request::{FromRequest, Outcome},
-    Request, State,
-};
+use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LatLong {
     pub lat: f64,
     pub lng: f64,
 }
 
 /// Full cache information
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cache {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
+    /// Short human readable code, assigned on insert
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Title of cache. Required for new caches, empty in partially loaded ones
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
     pub position: LatLong,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -35,100 +27,217 @@ pub struct Cache {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub owner_id: Option<i32>,
-}
 
-pub struct CacheDatabase {
-    client: Client,
-    collection: Collection<Cache>,
-}
+    /// Time of soft deletion. Deleted caches are hidden from all reads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
 
-impl CacheDatabase {
-    pub async fn insert_cache(&self, cache: Cache) -> Result<ObjectId, Error> {
-        let inserted_id = self.collection.insert_one(cache, None).await?.inserted_id;
-        Ok(inserted_id.as_object_id().unwrap())
-    }
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime>,
+    /// Time of last change including deletion and restore, used by delta sync
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime>,
+}
 
-    /// Return basic cache info: position and id
-    pub async fn get_caches(
-        &self,
-        user_id: Option<i32>,
-        bounds: Option<(LatLong, LatLong)>,
-    ) -> Result<Vec<Cache>, Error> {
-        let mut filter = Document::new();
-        if let Some(uid) = user_id {
-            filter.insert("owner_id", uid);
-        }
+/// Fields anyone may request in cache lists. Internal fields like
+/// `deleted_at` or `language` are never returned there
+pub const PUBLIC_FIELDS: &[&str] = &[
+    "code",
+    "name",
+    "position",
+    "description",
+    "hint",
+    "owner_id",
+    "created_at",
+    "updated_at",
+];
+
+/// Fields of cache lists unless other requested
+pub const LIST_FIELDS: &[&str] = &["code", "name", "position"];
+
+/// Order of cache lists. Caches with equal keys are ordered by id,
+/// so order is the same between requests
+#[derive(Debug, Clone)]
+pub struct CacheSort {
+    pub key: CacheSortKey,
+    pub descending: bool,
+}
 
-        if let Some((sw, ne)) = bounds {
-            filter.insert("position.lat", doc! { "$gte": sw.lat, "$lte": ne.lat });
-            filter.insert("position.lng", doc! { "$gte": sw.lng, "$lte": ne.lng });
-        }
+#[derive(Debug, Clone)]
+pub enum CacheSortKey {
+    CreatedAt,
+    UpdatedAt,
+    /// Distance from point, nearest first unless descending
+    Distance(LatLong),
+}
 
-        let options = FindOptions::builder()
-            .projection(doc! {
-                "_id": 1,
-                "position": 1,
-            })
-            .build();
+/// Mean radius of Earth in meters
+const EARTH_RADIUS: f64 = 6_371_000.0;
 
-        let cursor = self.collection.find(filter, options).await?;
+impl LatLong {
+    /// Great-circle distance in meters
+    pub fn distance_to(&self, other: &LatLong) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lng = (other.lng - self.lng).to_radians();
 
-        // TODO! Handle result error
-        let collected: Vec<_> = cursor.try_collect().await.unwrap();
-        Ok(collected)
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
     }
+}
 
-    pub async fn get_cache_by_id(&self, id: ObjectId) -> Result<Option<Cache>, Error> {
-        let filter = doc! {
-            "_id": id,
-        };
+/// Longest accepted cache name
+const MAX_NAME_LENGTH: usize = 100;
 
-        self.collection.find_one(filter, None).await
+impl Cache {
+    /// Checks fields given by user
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("Cache name is required".to_string());
+        }
+        if name.chars().count() > MAX_NAME_LENGTH {
+            return Err(format!(
+                "Cache name is longer than {} characters",
+                MAX_NAME_LENGTH
+            ));
+        }
+        Ok(())
     }
+}
 
-    pub async fn update_cache(&self, cache: Cache) -> Result<(), Error> {
-        let filter = doc! {
-            "_id": cache.id.expect("cannot update cache withou id"),
-        };
-
-        let update = doc! {
-            "$set": {
-                "position.lat": cache.position.lat,
-                "position.lng": cache.position.lng,
-                "description": cache.description,
-                "hint": cache.hint,
-            },
-        };
-
-        self.collection
-            .update_one(filter, update, None)
-            .await
-            .map(|_| ())
+/// Beginning of every short code
+const CODE_PREFIX: &str = "KT";
+
+/// Digits after prefix in codes of first caches
+const CODE_DIGITS: u32 = 4;
+
+/// Count of codes with `CODE_DIGITS` digits
+const CODE_SPACE: u64 = 36u64.pow(CODE_DIGITS);
+
+/// Coprime with `CODE_SPACE`, so multiplication shuffles codes without collisions
+const CODE_MULTIPLIER: u64 = 1_000_003;
+
+/// Short code of cache with given sequence number, like `KT1A2B`.
+/// Consecutive caches get unrelated codes, so codes are not guessed by counting
+pub fn short_code(number: u64) -> String {
+    let (mut value, digits) = if number < CODE_SPACE {
+        (number * CODE_MULTIPLIER % CODE_SPACE, CODE_DIGITS as usize)
+    } else {
+        // Longer codes never collide with shuffled short ones
+        (number, 0)
+    };
+
+    let mut code = Vec::new();
+    while value > 0 || code.len() < digits {
+        code.push(
+            char::from_digit((value % 36) as u32, 36)
+                .unwrap()
+                .to_ascii_uppercase(),
+        );
+        value /= 36;
     }
+    code.reverse();
 
-    pub async fn delete_cache_by_id(&self, id: ObjectId) -> Result<(), Error> {
-        let filter = doc! {
-            "_id": id,
-        };
-        self.collection.delete_one(filter, None).await.map(|_| ())
-    }
+    CODE_PREFIX.to_string() + &code.into_iter().collect::<String>()
 }
 
-#[async_trait]
-impl<'r> FromRequest<'r> for CacheDatabase {
-    type Error = ();
+/// Returns code in canonical form if value looks like short code
+pub fn parse_short_code(value: &str) -> Option<String> {
+    let code = value.trim().to_ascii_uppercase();
+    let digits = code.strip_prefix(CODE_PREFIX)?;
+    let valid =
+        digits.len() >= CODE_DIGITS as usize && digits.chars().all(|c| c.is_ascii_alphanumeric());
+    valid.then_some(code)
+}
 
-    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let client = req.guard::<&State<Client>>().await;
-        let clonned = Client::clone(client.unwrap());
+/// Cache found by text search with its relevance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoredCache {
+    #[serde(flatten)]
+    pub cache: Cache,
+    pub score: f64,
+}
 
-        let db_name = env::var("DATABASE_NAME").expect("DATABASE_NAME must be set");
-        let db = clonned.database(&db_name);
-        let collection = db.collection("cache");
+/// Language used to stem cache texts and search queries: Russian if text
+/// has Cyrillic letters, English otherwise
+pub fn text_language<'a>(texts: impl IntoIterator<Item = &'a str>) -> &'static str {
+    let cyrillic = texts
+        .into_iter()
+        .flat_map(str::chars)
+        .any(|c| matches!(c, 'а'..='я' | 'А'..='Я' | 'ё' | 'Ё'));
+    if cyrillic {
+        "russian"
+    } else {
+        "english"
+    }
+}
 
-        Outcome::Success(Self {
-            client: clonned,
-            collection,
+/// Remains of purged cache, kept so syncing clients learn about deletion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tombstone {
+    #[serde(rename = "_id")]
+    pub cache_id: ObjectId,
+    pub position: LatLong,
+    /// Time of deletion
+    pub updated_at: DateTime,
+}
+
+/// Former position of moved cache, kept so clients syncing the area it
+/// left learn it is gone from there
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Departure {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub cache_id: ObjectId,
+    pub position: LatLong,
+    /// Time of move
+    pub updated_at: DateTime,
+}
+
+impl Departure {
+    /// Departure of cache updated from `before` to `after`, if it moved
+    pub fn of(before: &Cache, after: &Cache) -> Option<Self> {
+        if before.position == after.position {
+            return None;
+        }
+        Some(Self {
+            id: None,
+            cache_id: before.id?,
+            position: before.position.clone(),
+            updated_at: after.updated_at.unwrap_or_else(DateTime::now),
         })
     }
 }
+
+/// Change of cache reported to syncing clients
+#[derive(Debug, Clone)]
+pub struct CacheChange {
+    pub id: ObjectId,
+    pub deleted: bool,
+}
+
+/// Parses bounds given as `min_lat,min_lng,max_lat,max_lng`.
+/// Returns south-west and north-east corners
+pub fn parse_bbox(value: &str) -> Result<(LatLong, LatLong), String> {
+    let numbers = value
+        .split(',')
+        .map(|n| n.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.to_string())?;
+
+    let [min_lat, min_lng, max_lat, max_lng] = numbers[..] else {
+        return Err("expected 4 comma separated numbers".to_string());
+    };
+
+    Ok((
+        LatLong {
+            lat: min_lat.min(max_lat),
+            lng: min_lng.min(max_lng),
+        },
+        LatLong {
+            lat: min_lat.max(max_lat),
+            lng: min_lng.max(max_lng),
+        },
+    ))
+}
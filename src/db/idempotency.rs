@@ -0,0 +1,25 @@
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+/// Request made with idempotency key and its response, once it is known
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdempotencyRecord {
+    /// User id and key, as keys of different users may be the same
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// Hash of endpoint and request body, used to detect reuse of key
+    pub fingerprint: String,
+    /// Missing while first request is processed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<StoredResponse>,
+    /// Record is removed after this time. Until response is saved it is
+    /// a short lease, so key of interrupted request is not held for long
+    pub expires_at: DateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub status: u16,
+    /// JSON body as sent
+    pub body: String,
+}
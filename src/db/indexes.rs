@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use super::{setup::SetupError, TOMBSTONE_RETENTION};
+use mongodb::{
+    bson::{doc, Document},
+    error::{Error, ErrorKind},
+    options::IndexOptions,
+    Database, IndexModel,
+};
+
+/// Index required by service queries
+struct IndexSpec {
+    collection: &'static str,
+    name: &'static str,
+    keys: fn() -> Document,
+    unique: bool,
+    /// Documents are removed by MongoDB after this time since indexed date
+    expire_after: Option<Duration>,
+}
+
+/// Indexes created on launch. Changing keys of existing index requires new name,
+/// otherwise MongoDB rejects it as conflicting with the existing one
+const INDEXES: &[IndexSpec] = &[
+    // Bounds search in `get_caches`
+    IndexSpec {
+        collection: "cache",
+        name: "position_lat_lng",
+        keys: || doc! { "position.lat": 1, "position.lng": 1 },
+        unique: false,
+        expire_after: None,
+    },
+    // Geo queries over GeoJSON point kept next to `position`
+    IndexSpec {
+        collection: "cache",
+        name: "location_2dsphere",
+        keys: || doc! { "location": "2dsphere" },
+        unique: false,
+        expire_after: None,
+    },
+    // Text search. Collection can have only one text index, so changing it
+    // requires dropping the old one first
+    IndexSpec {
+        collection: "cache",
+        name: "text_search",
+        keys: || doc! { "name": "text", "description": "text", "hint": "text" },
+        unique: false,
+        expire_after: None,
+    },
+    // Lookup by short code, which must never repeat
+    IndexSpec {
+        collection: "cache",
+        name: "code",
+        keys: || doc! { "code": 1 },
+        unique: true,
+        expire_after: None,
+    },
+    // Caches of single user
+    IndexSpec {
+        collection: "cache",
+        name: "owner_id",
+        keys: || doc! { "owner_id": 1 },
+        unique: false,
+        expire_after: None,
+    },
+    // Soft delete status and purge of old deleted caches
+    IndexSpec {
+        collection: "cache",
+        name: "deleted_at",
+        keys: || doc! { "deleted_at": 1 },
+        unique: false,
+        expire_after: None,
+    },
+    IndexSpec {
+        collection: "revisions",
+        name: "cache_id_revision",
+        keys: || doc! { "cache_id": 1, "revision": 1 },
+        unique: true,
+        expire_after: None,
+    },
+    IndexSpec {
+        collection: "audit",
+        name: "cache_id_timestamp",
+        keys: || doc! { "cache_id": 1, "timestamp": 1, "_id": 1 },
+        unique: false,
+        expire_after: None,
+    },
+    // Delta sync
+    IndexSpec {
+        collection: "cache",
+        name: "updated_at",
+        keys: || doc! { "updated_at": 1 },
+        unique: false,
+        expire_after: None,
+    },
+    IndexSpec {
+        collection: "tombstones",
+        name: "updated_at_ttl",
+        keys: || doc! { "updated_at": 1 },
+        unique: false,
+        expire_after: Some(TOMBSTONE_RETENTION),
+    },
+    IndexSpec {
+        collection: "departures",
+        name: "updated_at_ttl",
+        keys: || doc! { "updated_at": 1 },
+        unique: false,
+        expire_after: Some(TOMBSTONE_RETENTION),
+    },
+    // Responses of requests with idempotency keys, removed when expired
+    IndexSpec {
+        collection: "idempotency",
+        name: "expires_at_ttl",
+        keys: || doc! { "expires_at": 1 },
+        unique: false,
+        expire_after: Some(Duration::ZERO),
+    },
+    // Delivery log of webhook, kept for a week
+    IndexSpec {
+        collection: "webhook_deliveries",
+        name: "timestamp_ttl",
+        keys: || doc! { "timestamp": 1 },
+        unique: false,
+        expire_after: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+    },
+    IndexSpec {
+        collection: "webhook_deliveries",
+        name: "subscription_id_timestamp",
+        keys: || doc! { "subscription_id": 1, "timestamp": -1, "_id": -1 },
+        unique: false,
+        expire_after: None,
+    },
+];
+
+const TEXT_INDEX: &str = "text_search";
+
+/// Code of error listing indexes of collection which does not exist yet
+const NAMESPACE_NOT_FOUND: i32 = 26;
+
+/// Creates missing indexes. Returns names of created indexes as `collection.index`
+pub async fn ensure_indexes(database: &Database) -> Result<Vec<String>, SetupError> {
+    let mut created = Vec::new();
+
+    for spec in INDEXES {
+        let collection = database.collection::<Document>(spec.collection);
+        let existing = match collection.list_index_names().await {
+            Ok(names) => names,
+            Err(err) if is_namespace_not_found(&err) => Vec::new(),
+            Err(err) => {
+                let context = format!("failed to list indexes of '{}'", spec.collection);
+                return Err(SetupError::new(context, err));
+            }
+        };
+
+        // Creating existing index is no-op, but it is skipped to log only new ones
+        if existing.iter().any(|name| name == spec.name) {
+            continue;
+        }
+
+        let mut options = IndexOptions::builder()
+            .name(spec.name.to_string())
+            .unique(spec.unique)
+            .expire_after(spec.expire_after)
+            .build();
+        if spec.name == TEXT_INDEX {
+            // Each cache is stemmed in language stored in its `language` field
+            options.weights = Some(doc! { "name": 10, "description": 5, "hint": 1 });
+            options.default_language = Some("english".to_string());
+            options.language_override = Some("language".to_string());
+        }
+        let index = IndexModel::builder()
+            .keys((spec.keys)())
+            .options(options)
+            .build();
+
+        collection.create_index(index, None).await.map_err(|err| {
+            let context = format!(
+                "failed to create index '{}' on '{}', existing data or indexes may be incompatible",
+                spec.name, spec.collection
+            );
+            SetupError::new(context, err)
+        })?;
+        created.push(format!("{}.{}", spec.collection, spec.name));
+    }
+
+    Ok(created)
+}
+
+fn is_namespace_not_found(err: &Error) -> bool {
+    matches!(&*err.kind, ErrorKind::Command(err) if err.code == NAMESPACE_NOT_FOUND)
+}
@@ -0,0 +1,549 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+};
+
+use mongodb::bson::{from_document, oid::ObjectId, to_document, DateTime, Document};
+
+use super::{
+    cache::{short_code, Departure, Tombstone},
+    AuditEntry, AuditStore, Cache, CacheChange, CacheSort, CacheSortKey, CacheStore, CacheWrite,
+    CacheWritten, IdempotencyRecord, IdempotencyStore, LatLong, Revision, ScoredCache, StoreResult,
+    StoredResponse, WebhookDelivery, WebhookStore, WebhookSubscription,
+};
+
+/// Delivery attempts kept for each webhook subscription
+const MAX_DELIVERIES: usize = 100;
+
+#[derive(Debug, Default)]
+struct Collections {
+    /// Number of last assigned short code
+    codes: u64,
+    caches: BTreeMap<ObjectId, Cache>,
+    revisions: Vec<Revision>,
+    tombstones: Vec<Tombstone>,
+    departures: Vec<Departure>,
+    audit: Vec<AuditEntry>,
+    webhooks: BTreeMap<ObjectId, WebhookSubscription>,
+    deliveries: Vec<WebhookDelivery>,
+    idempotency: HashMap<String, IdempotencyRecord>,
+}
+
+/// Storage keeping everything in process memory. Data is lost on restart,
+/// so it is meant for tests and local demos only
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    collections: Mutex<Collections>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn in_bounds(position: &LatLong, (sw, ne): &(LatLong, LatLong)) -> bool {
+    (sw.lat..=ne.lat).contains(&position.lat) && (sw.lng..=ne.lng).contains(&position.lng)
+}
+
+/// Cache with id, position and given fields only, like MongoDB projection
+fn list_item(cache: &Cache, fields: &[&str]) -> Cache {
+    let document: Document = to_document(cache)
+        .expect("cache is always serializable")
+        .into_iter()
+        .filter(|(key, _)| key == "_id" || key == "position" || fields.contains(&key.as_str()))
+        .collect();
+    from_document(document).expect("projected cache is always deserializable")
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Relevance of cache for search terms. Words match if they share beginning,
+/// which roughly imitates stemming of MongoDB text index
+fn text_score(cache: &Cache, terms: &[String]) -> f64 {
+    let fields = [
+        (Some(cache.name.as_str()), 10.0),
+        (cache.description.as_deref(), 5.0),
+        (cache.hint.as_deref(), 1.0),
+    ];
+
+    let mut score = 0.0;
+    for (text, weight) in fields {
+        for word in words(text.unwrap_or_default()) {
+            let matches = terms.iter().any(|term| {
+                let stem: String = term.chars().take(term.chars().count().max(5) - 2).collect();
+                word.starts_with(&stem)
+            });
+            if matches {
+                score += weight;
+            }
+        }
+    }
+    score
+}
+
+impl Collections {
+    fn insert(&mut self, mut cache: Cache) -> Cache {
+        self.codes += 1;
+        cache.code = Some(short_code(self.codes));
+
+        let id = *cache.id.get_or_insert_with(ObjectId::new);
+        self.caches.insert(id, cache.clone());
+        cache
+    }
+
+    /// Updates active cache and keeps its previous version as revision
+    fn update(&mut self, cache: Cache) -> Option<Cache> {
+        let id = cache.id.expect("cannot update cache withou id");
+        let stored = self
+            .caches
+            .get_mut(&id)
+            .filter(|stored| stored.deleted_at.is_none())?;
+
+        let previous = stored.clone();
+        stored.name = cache.name;
+        stored.position = cache.position;
+        stored.description = cache.description;
+        stored.hint = cache.hint;
+        stored.updated_at = Some(DateTime::now());
+        let departure = Departure::of(&previous, stored);
+        self.departures.extend(departure);
+
+        let number = self
+            .revisions
+            .iter()
+            .filter(|revision| revision.cache_id == id)
+            .count();
+        self.revisions.push(Revision {
+            id: Some(ObjectId::new()),
+            cache_id: id,
+            revision: number as u32 + 1,
+            created_at: DateTime::now(),
+            cache: previous.clone(),
+        });
+
+        Some(previous)
+    }
+
+    /// Soft deletes active cache
+    fn delete(&mut self, id: ObjectId) -> Option<Cache> {
+        let stored = self
+            .caches
+            .get_mut(&id)
+            .filter(|stored| stored.deleted_at.is_none())?;
+
+        let previous = stored.clone();
+        let now = DateTime::now();
+        stored.deleted_at = Some(now);
+        stored.updated_at = Some(now);
+        Some(previous)
+    }
+
+    fn active_caches<'a>(
+        &'a self,
+        user_id: Option<i32>,
+        bounds: &'a Option<(LatLong, LatLong)>,
+    ) -> impl Iterator<Item = &'a Cache> + 'a {
+        self.caches
+            .values()
+            .filter(|cache| cache.deleted_at.is_none())
+            .filter(move |cache| user_id.is_none() || cache.owner_id == user_id)
+            .filter(move |cache| {
+                bounds
+                    .as_ref()
+                    .is_none_or(|b| in_bounds(&cache.position, b))
+            })
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryStore {
+    async fn insert_cache(&self, cache: Cache) -> StoreResult<Cache> {
+        Ok(self.collections.lock().unwrap().insert(cache))
+    }
+
+    async fn get_caches(
+        &self,
+        user_id: Option<i32>,
+        bounds: Option<(LatLong, LatLong)>,
+        fields: &[&str],
+        sort: Option<&CacheSort>,
+    ) -> StoreResult<Vec<Cache>> {
+        let collections = self.collections.lock().unwrap();
+        let mut caches: Vec<_> = collections.active_caches(user_id, &bounds).collect();
+
+        if let Some(sort) = sort {
+            caches.sort_by(|a, b| {
+                let order = match &sort.key {
+                    CacheSortKey::CreatedAt => a.created_at.cmp(&b.created_at),
+                    CacheSortKey::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+                    CacheSortKey::Distance(point) => point
+                        .distance_to(&a.position)
+                        .total_cmp(&point.distance_to(&b.position)),
+                };
+                let order = order.then(a.id.cmp(&b.id));
+                if sort.descending {
+                    order.reverse()
+                } else {
+                    order
+                }
+            });
+        }
+
+        Ok(caches
+            .into_iter()
+            .map(|cache| list_item(cache, fields))
+            .collect())
+    }
+
+    async fn search_caches(
+        &self,
+        text: &str,
+        user_id: Option<i32>,
+        bounds: Option<(LatLong, LatLong)>,
+        fields: &[&str],
+    ) -> StoreResult<Vec<ScoredCache>> {
+        let terms: Vec<_> = words(text).collect();
+        let collections = self.collections.lock().unwrap();
+
+        let mut found: Vec<_> = collections
+            .active_caches(user_id, &bounds)
+            .map(|cache| ScoredCache {
+                cache: list_item(cache, fields),
+                score: text_score(cache, &terms),
+            })
+            .filter(|found| found.score > 0.0)
+            .collect();
+        found.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(a.cache.id.cmp(&b.cache.id))
+        });
+        Ok(found)
+    }
+
+    async fn get_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>> {
+        let collections = self.collections.lock().unwrap();
+        Ok(collections
+            .caches
+            .get(&id)
+            .filter(|cache| cache.deleted_at.is_none())
+            .cloned())
+    }
+
+    async fn get_caches_by_ids(
+        &self,
+        ids: &[ObjectId],
+        codes: &[String],
+    ) -> StoreResult<Vec<Cache>> {
+        let collections = self.collections.lock().unwrap();
+        Ok(collections
+            .caches
+            .values()
+            .filter(|cache| cache.deleted_at.is_none())
+            .filter(|cache| {
+                cache.id.is_some_and(|id| ids.contains(&id))
+                    || cache.code.as_ref().is_some_and(|code| codes.contains(code))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn get_cache_id_by_code(&self, code: &str) -> StoreResult<Option<ObjectId>> {
+        let collections = self.collections.lock().unwrap();
+        Ok(collections
+            .caches
+            .values()
+            .find(|cache| cache.code.as_deref() == Some(code))
+            .and_then(|cache| cache.id))
+    }
+
+    async fn get_any_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>> {
+        Ok(self.collections.lock().unwrap().caches.get(&id).cloned())
+    }
+
+    async fn update_cache(&self, cache: Cache) -> StoreResult<Option<Cache>> {
+        Ok(self.collections.lock().unwrap().update(cache))
+    }
+
+    async fn bulk_write(&self, writes: Vec<CacheWrite>) -> StoreResult<Vec<CacheWritten>> {
+        let mut collections = self.collections.lock().unwrap();
+        let written = writes
+            .into_iter()
+            .map(|write| match write {
+                CacheWrite::Insert(cache) => CacheWritten::Inserted(collections.insert(cache)),
+                CacheWrite::Update(cache) => {
+                    let id = cache.id.expect("cannot update cache withou id");
+                    match collections.update(cache) {
+                        Some(before) => CacheWritten::Updated {
+                            before,
+                            after: collections.caches[&id].clone(),
+                        },
+                        None => CacheWritten::NotFound,
+                    }
+                }
+                CacheWrite::Delete(id) => match collections.delete(id) {
+                    Some(before) => CacheWritten::Deleted(before),
+                    None => CacheWritten::NotFound,
+                },
+            })
+            .collect();
+        Ok(written)
+    }
+
+    async fn get_revisions(&self, id: ObjectId) -> StoreResult<Vec<Revision>> {
+        let collections = self.collections.lock().unwrap();
+        let mut revisions: Vec<_> = collections
+            .revisions
+            .iter()
+            .filter(|revision| revision.cache_id == id)
+            .cloned()
+            .collect();
+        revisions.sort_by_key(|revision| revision.revision);
+        Ok(revisions)
+    }
+
+    async fn get_revision(&self, id: ObjectId, number: u32) -> StoreResult<Option<Revision>> {
+        let collections = self.collections.lock().unwrap();
+        Ok(collections
+            .revisions
+            .iter()
+            .find(|revision| revision.cache_id == id && revision.revision == number)
+            .cloned())
+    }
+
+    async fn delete_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>> {
+        Ok(self.collections.lock().unwrap().delete(id))
+    }
+
+    async fn restore_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>> {
+        let mut collections = self.collections.lock().unwrap();
+        let Some(stored) = collections
+            .caches
+            .get_mut(&id)
+            .filter(|stored| stored.deleted_at.is_some())
+        else {
+            return Ok(None);
+        };
+
+        stored.deleted_at = None;
+        stored.updated_at = Some(DateTime::now());
+        Ok(Some(stored.clone()))
+    }
+
+    async fn purge_deleted(&self, deleted_before: DateTime) -> StoreResult<u64> {
+        let mut collections = self.collections.lock().unwrap();
+        let ids: Vec<_> = collections
+            .caches
+            .iter()
+            .filter(|(_, cache)| cache.deleted_at.is_some_and(|at| at < deleted_before))
+            .map(|(id, _)| *id)
+            .collect();
+
+        collections
+            .revisions
+            .retain(|revision| !ids.contains(&revision.cache_id));
+        for id in &ids {
+            if let Some(cache) = collections.caches.remove(id) {
+                collections.tombstones.push(Tombstone {
+                    cache_id: *id,
+                    position: cache.position,
+                    updated_at: cache.deleted_at.unwrap_or_else(DateTime::now),
+                });
+            }
+        }
+        Ok(ids.len() as u64)
+    }
+
+    async fn get_changes(
+        &self,
+        since: Option<DateTime>,
+        bounds: Option<(LatLong, LatLong)>,
+    ) -> StoreResult<Vec<CacheChange>> {
+        let collections = self.collections.lock().unwrap();
+        let in_area = |position: &LatLong| bounds.as_ref().is_none_or(|b| in_bounds(position, b));
+
+        let Some(since) = since else {
+            return Ok(collections
+                .caches
+                .values()
+                .filter(|cache| cache.deleted_at.is_none() && in_area(&cache.position))
+                .map(|cache| CacheChange {
+                    id: cache.id.expect("stored cache has id"),
+                    deleted: false,
+                })
+                .collect());
+        };
+
+        let caches = collections
+            .caches
+            .values()
+            .filter(|cache| cache.updated_at.is_some_and(|at| at > since))
+            .filter(|cache| in_area(&cache.position))
+            .map(|cache| CacheChange {
+                id: cache.id.expect("stored cache has id"),
+                deleted: cache.deleted_at.is_some(),
+            });
+        let tombstones = collections
+            .tombstones
+            .iter()
+            .filter(|t| t.updated_at > since && in_area(&t.position))
+            .map(|t| CacheChange {
+                id: t.cache_id,
+                deleted: true,
+            });
+        let mut changes: Vec<_> = caches.chain(tombstones).collect();
+
+        // Caches which moved within area or back into it are reported as they are now
+        let reported: HashSet<ObjectId> = changes.iter().map(|c| c.id).collect();
+        let departed: HashSet<ObjectId> = collections
+            .departures
+            .iter()
+            .filter(|d| d.updated_at > since && in_area(&d.position))
+            .map(|d| d.cache_id)
+            .filter(|id| !reported.contains(id))
+            .collect();
+        changes.extend(
+            departed
+                .into_iter()
+                .map(|id| CacheChange { id, deleted: true }),
+        );
+        Ok(changes)
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuditStore for MemoryStore {
+    async fn record(&self, mut entry: AuditEntry) -> StoreResult<()> {
+        entry.id.get_or_insert_with(ObjectId::new);
+        self.collections.lock().unwrap().audit.push(entry);
+        Ok(())
+    }
+
+    async fn get_history(&self, cache_id: ObjectId) -> StoreResult<Vec<AuditEntry>> {
+        let collections = self.collections.lock().unwrap();
+        let mut history: Vec<_> = collections
+            .audit
+            .iter()
+            .filter(|entry| entry.cache_id == cache_id)
+            .cloned()
+            .collect();
+        history.sort_by_key(|entry| (entry.timestamp, entry.id));
+        Ok(history)
+    }
+}
+
+#[async_trait]
+impl WebhookStore for MemoryStore {
+    async fn insert_subscription(
+        &self,
+        mut subscription: WebhookSubscription,
+    ) -> StoreResult<ObjectId> {
+        let id = *subscription.id.get_or_insert_with(ObjectId::new);
+        self.collections
+            .lock()
+            .unwrap()
+            .webhooks
+            .insert(id, subscription);
+        Ok(id)
+    }
+
+    async fn get_subscriptions(&self) -> StoreResult<Vec<WebhookSubscription>> {
+        let collections = self.collections.lock().unwrap();
+        Ok(collections.webhooks.values().cloned().collect())
+    }
+
+    async fn delete_subscription(&self, id: ObjectId) -> StoreResult<bool> {
+        let mut collections = self.collections.lock().unwrap();
+        collections
+            .deliveries
+            .retain(|delivery| delivery.subscription_id != id);
+        Ok(collections.webhooks.remove(&id).is_some())
+    }
+
+    async fn record_delivery(&self, mut delivery: WebhookDelivery) -> StoreResult<()> {
+        delivery.id.get_or_insert_with(ObjectId::new);
+        let subscription_id = delivery.subscription_id;
+
+        let mut collections = self.collections.lock().unwrap();
+        collections.deliveries.push(delivery);
+
+        let kept = collections
+            .deliveries
+            .iter()
+            .filter(|delivery| delivery.subscription_id == subscription_id)
+            .count();
+        if kept > MAX_DELIVERIES {
+            let oldest = collections
+                .deliveries
+                .iter()
+                .position(|delivery| delivery.subscription_id == subscription_id)
+                .unwrap();
+            collections.deliveries.remove(oldest);
+        }
+        Ok(())
+    }
+
+    async fn get_deliveries(
+        &self,
+        subscription_id: ObjectId,
+        limit: i64,
+    ) -> StoreResult<Vec<WebhookDelivery>> {
+        let collections = self.collections.lock().unwrap();
+        Ok(collections
+            .deliveries
+            .iter()
+            .rev()
+            .filter(|delivery| delivery.subscription_id == subscription_id)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryStore {
+    async fn reserve_key(
+        &self,
+        record: IdempotencyRecord,
+    ) -> StoreResult<Option<IdempotencyRecord>> {
+        let mut collections = self.collections.lock().unwrap();
+        let now = DateTime::now();
+        collections
+            .idempotency
+            .retain(|_, record| record.expires_at > now);
+
+        if let Some(existing) = collections.idempotency.get(&record.id) {
+            return Ok(Some(existing.clone()));
+        }
+        collections.idempotency.insert(record.id.clone(), record);
+        Ok(None)
+    }
+
+    async fn complete_key(
+        &self,
+        id: &str,
+        response: StoredResponse,
+        expires_at: DateTime,
+    ) -> StoreResult<()> {
+        let mut collections = self.collections.lock().unwrap();
+        if let Some(record) = collections.idempotency.get_mut(id) {
+            record.response = Some(response);
+            record.expires_at = expires_at;
+        }
+        Ok(())
+    }
+
+    async fn release_key(&self, id: &str) -> StoreResult<()> {
+        self.collections.lock().unwrap().idempotency.remove(id);
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashSet;
+
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, DateTime, Document},
+    error::Error,
+    options::FindOptions,
+    Database,
+};
+use rocket::futures::{future::BoxFuture, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cache::text_language,
+    mongo::{next_code, REVISION_COUNTER},
+};
+
+/// Collection keeping names of applied migrations
+const MIGRATIONS_COLLECTION: &str = "_migrations";
+
+/// Named step rewriting existing documents. Steps must be idempotent
+/// as several service instances may run them at the same time
+struct Migration {
+    name: &'static str,
+    description: &'static str,
+    /// Applies step and returns count of changed documents.
+    /// In dry run nothing is changed and count of documents to change is returned
+    apply: for<'a> fn(&'a Database, bool) -> BoxFuture<'a, Result<u64, Error>>,
+}
+
+/// Migrations in order of application. New steps are added to the end
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "0001_backfill_location",
+        description: "Add GeoJSON point `location` built from `position.lat/lng`",
+        apply: |database, dry_run| Box::pin(backfill_location(database, dry_run)),
+    },
+    Migration {
+        name: "0002_backfill_timestamps",
+        description: "Set `created_at` and `updated_at` from creation time of id",
+        apply: |database, dry_run| Box::pin(backfill_timestamps(database, dry_run)),
+    },
+    Migration {
+        name: "0003_backfill_language",
+        description: "Set `language` used by text index from cache texts",
+        apply: |database, dry_run| Box::pin(backfill_language(database, dry_run)),
+    },
+    Migration {
+        name: "0004_backfill_codes",
+        description: "Assign short `code` to caches and use it as `name` where missing",
+        apply: |database, dry_run| Box::pin(backfill_codes(database, dry_run)),
+    },
+    Migration {
+        name: "0005_backfill_revision_counts",
+        description: "Set `revision_count` of caches from their last revision number",
+        apply: |database, dry_run| Box::pin(backfill_revision_counts(database, dry_run)),
+    },
+    Migration {
+        name: "0006_drop_numeric_event_ids",
+        description: "Remove webhook deliveries of events with numeric ids, which repeated",
+        apply: |database, dry_run| Box::pin(drop_numeric_event_ids(database, dry_run)),
+    },
+    Migration {
+        name: "0007_recompute_language",
+        description: "Set `language` of caches again, taking their names into account",
+        apply: |database, dry_run| Box::pin(recompute_language(database, dry_run)),
+    },
+];
+
+/// Record of applied migration
+#[derive(Debug, Serialize, Deserialize)]
+struct AppliedMigration {
+    #[serde(rename = "_id")]
+    name: String,
+    applied_at: DateTime,
+    documents: u64,
+}
+
+/// Result of one pending migration
+#[derive(Debug)]
+pub struct MigrationResult {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Changed documents, or documents to change in dry run
+    pub documents: u64,
+}
+
+/// Applies pending migrations in order and records them as applied.
+/// In dry run only reports what would be changed
+pub async fn run_migrations(
+    database: &Database,
+    dry_run: bool,
+) -> Result<Vec<MigrationResult>, Error> {
+    let collection = database.collection::<AppliedMigration>(MIGRATIONS_COLLECTION);
+    let applied: HashSet<String> = collection
+        .find(None, None)
+        .await?
+        .map_ok(|migration| migration.name)
+        .try_collect()
+        .await?;
+
+    let mut results = Vec::new();
+    for migration in MIGRATIONS {
+        if applied.contains(migration.name) {
+            continue;
+        }
+
+        let documents = (migration.apply)(database, dry_run).await?;
+        if !dry_run {
+            let record = AppliedMigration {
+                name: migration.name.to_string(),
+                applied_at: DateTime::now(),
+                documents,
+            };
+            collection.insert_one(record, None).await?;
+        }
+
+        results.push(MigrationResult {
+            name: migration.name,
+            description: migration.description,
+            documents,
+        });
+    }
+
+    Ok(results)
+}
+
+async fn backfill_location(database: &Database, dry_run: bool) -> Result<u64, Error> {
+    let collection = database.collection::<Document>("cache");
+    let filter = doc! {
+        "location": { "$exists": false },
+        "position.lat": { "$type": "number" },
+        "position.lng": { "$type": "number" },
+    };
+
+    if dry_run {
+        return collection.count_documents(filter, None).await;
+    }
+
+    // GeoJSON keeps longitude first
+    let update = vec![doc! {
+        "$set": {
+            "location": {
+                "type": "Point",
+                "coordinates": ["$position.lng", "$position.lat"],
+            },
+        },
+    }];
+    let result = collection.update_many(filter, update, None).await?;
+    Ok(result.modified_count)
+}
+
+async fn backfill_timestamps(database: &Database, dry_run: bool) -> Result<u64, Error> {
+    let collection = database.collection::<Document>("cache");
+    let filter = doc! {
+        "created_at": { "$exists": false },
+    };
+
+    if dry_run {
+        return collection.count_documents(filter, None).await;
+    }
+
+    // Deleted caches changed when they were deleted
+    let update = vec![doc! {
+        "$set": {
+            "created_at": { "$toDate": "$_id" },
+            "updated_at": { "$ifNull": ["$deleted_at", { "$toDate": "$_id" }] },
+        },
+    }];
+    let result = collection.update_many(filter, update, None).await?;
+    Ok(result.modified_count)
+}
+
+async fn backfill_language(database: &Database, dry_run: bool) -> Result<u64, Error> {
+    let filter = doc! {
+        "language": { "$exists": false },
+    };
+    set_language(database, filter, dry_run).await
+}
+
+/// First version of `0003_backfill_language` left cache names out
+async fn recompute_language(database: &Database, dry_run: bool) -> Result<u64, Error> {
+    set_language(database, doc! {}, dry_run).await
+}
+
+/// Sets `language` of caches matching `filter` by `text_language`, over the
+/// same texts as language of new caches. Only caches whose language differs
+/// are written and counted
+async fn set_language(database: &Database, filter: Document, dry_run: bool) -> Result<u64, Error> {
+    let collection = database.collection::<Document>("cache");
+    let options = FindOptions::builder()
+        .projection(doc! { "name": 1, "description": 1, "hint": 1, "language": 1 })
+        .build();
+    let mut cursor = collection.find(filter, options).await?;
+
+    let mut changed = 0;
+    while let Some(cache) = cursor.try_next().await? {
+        let texts = ["name", "description", "hint"]
+            .into_iter()
+            .filter_map(|field| cache.get_str(field).ok());
+        let language = text_language(texts);
+        if cache.get_str("language").ok() == Some(language) {
+            continue;
+        }
+
+        if !dry_run {
+            let filter = doc! { "_id": cache.get("_id") };
+            let update = doc! { "$set": { "language": language } };
+            collection.update_one(filter, update, None).await?;
+        }
+        changed += 1;
+    }
+    Ok(changed)
+}
+
+async fn backfill_codes(database: &Database, dry_run: bool) -> Result<u64, Error> {
+    let collection = database.collection::<Document>("cache");
+    let filter = doc! {
+        "code": { "$exists": false },
+    };
+
+    if dry_run {
+        return collection.count_documents(filter, None).await;
+    }
+
+    // Codes come from the same counter as codes of new caches, so each is
+    // assigned separately. Oldest caches get codes first
+    let options = FindOptions::builder()
+        .projection(doc! { "_id": 1 })
+        .sort(doc! { "_id": 1 })
+        .build();
+    let ids: Vec<ObjectId> = collection
+        .find(filter, options)
+        .await?
+        .try_filter_map(|cache| async move { Ok(cache.get_object_id("_id").ok()) })
+        .try_collect()
+        .await?;
+
+    let mut changed = 0;
+    for id in ids {
+        let code = next_code(database).await?;
+        // Cache may already get code from another instance running migration
+        let filter = doc! { "_id": id, "code": { "$exists": false } };
+        let update = vec![doc! {
+            "$set": {
+                "code": &code,
+                "name": { "$ifNull": ["$name", &code] },
+            },
+        }];
+        changed += collection
+            .update_one(filter, update, None)
+            .await?
+            .modified_count;
+    }
+    Ok(changed)
+}
+
+async fn backfill_revision_counts(database: &Database, dry_run: bool) -> Result<u64, Error> {
+    let collection = database.collection::<Document>("cache");
+    let pipeline = [doc! {
+        "$group": { "_id": "$cache_id", "last": { "$max": "$revision" } },
+    }];
+    let last_revisions: Vec<Document> = database
+        .collection::<Document>("revisions")
+        .aggregate(pipeline, None)
+        .await?
+        .try_collect()
+        .await?;
+
+    // Caches without revisions get the counter on first update
+    let mut changed = 0;
+    for last in last_revisions {
+        let number = match last.get("last") {
+            Some(Bson::Int64(number)) => *number,
+            Some(Bson::Int32(number)) => *number as i64,
+            _ => continue,
+        };
+        let Ok(id) = last.get_object_id("_id") else {
+            continue;
+        };
+        let filter = doc! { "_id": id, REVISION_COUNTER: { "$exists": false } };
+        if dry_run {
+            changed += collection.count_documents(filter, None).await?;
+            continue;
+        }
+
+        let update = doc! { "$set": { REVISION_COUNTER: number } };
+        changed += collection
+            .update_one(filter, update, None)
+            .await?
+            .modified_count;
+    }
+    Ok(changed)
+}
+
+async fn drop_numeric_event_ids(database: &Database, dry_run: bool) -> Result<u64, Error> {
+    let collection = database.collection::<Document>("webhook_deliveries");
+    let filter = doc! {
+        "event_id": { "$not": { "$type": "objectId" } },
+    };
+
+    if dry_run {
+        return collection.count_documents(filter, None).await;
+    }
+
+    // Delivery log is kept for a week only, so old attempts are not worth converting
+    let result = collection.delete_many(filter, None).await?;
+    Ok(result.deleted_count)
+}
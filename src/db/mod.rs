@@ -1,41 +1,146 @@
-use std::env;
+use std::{sync::Arc, time::Duration};
 
-use mongodb::Client;
-use rocket::serde::json::Json;
+use mongodb::{Client, Database};
 use rocket::{Build, Rocket};
 
+use crate::config::{Config, DatabaseConfig, StorageBackend};
+
+mod admin;
+pub use admin::AdminDatabase;
+pub use admin::CacheFilter;
+pub use admin::CacheStats;
+
+mod audit;
+pub use audit::AuditAction;
+pub use audit::AuditEntry;
+pub use audit::OPERATOR_ACTOR_ID;
+
 mod cache;
+pub use cache::parse_bbox;
+pub use cache::parse_short_code;
+pub use cache::text_language;
 pub use cache::Cache;
-pub use cache::CacheDatabase;
+pub use cache::CacheChange;
+pub use cache::CacheSort;
+pub use cache::CacheSortKey;
 pub use cache::LatLong;
-use serde_json::{json, Value};
+pub use cache::ScoredCache;
+pub use cache::LIST_FIELDS;
+pub use cache::PUBLIC_FIELDS;
+
+mod idempotency;
+pub use idempotency::IdempotencyRecord;
+pub use idempotency::StoredResponse;
+
+mod indexes;
+
+mod memory;
+pub use memory::MemoryStore;
+
+mod migrations;
+pub use migrations::run_migrations;
+pub use migrations::MigrationResult;
+
+mod mongo;
+pub use mongo::MongoStore;
+
+mod purge;
+pub use purge::RocketSchedulePurge;
+
+mod revision;
+pub use revision::Revision;
+
+mod setup;
+pub use setup::DatabaseSetup;
+
+mod store;
+pub use store::AuditDatabase;
+pub use store::AuditStore;
+pub use store::CacheDatabase;
+pub use store::CacheStore;
+pub use store::CacheWrite;
+pub use store::CacheWritten;
+pub use store::IdempotencyDatabase;
+pub use store::IdempotencyStore;
+pub use store::StoreResult;
+pub use store::WebhookDatabase;
+pub use store::WebhookStore;
+
+mod webhook;
+pub use webhook::WebhookDelivery;
+pub use webhook::WebhookSubscription;
+
+/// Time tombstones of purged caches and departures of moved caches are
+/// kept. Clients which did not sync for longer have to download caches again
+pub const TOMBSTONE_RETENTION: Duration = Duration::from_secs(180 * 24 * 60 * 60);
 
 #[async_trait]
 pub trait RockerDatabaseConnect {
-    async fn connect_database(self) -> Self;
+    async fn connect_database(self, config: &Config) -> Self;
 }
 
 #[async_trait]
 impl RockerDatabaseConnect for Rocket<Build> {
-    async fn connect_database(self) -> Self {
-        let db_path = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let client = Client::with_uri_str(&db_path).await.unwrap();
+    /// Manages cache and audit storage selected by `storage_backend` setting.
+    /// Pending migrations and missing indexes of MongoDB storage are applied
+    /// on launch, see `setup::on_ignite`
+    async fn connect_database(self, config: &Config) -> Self {
+        match config.storage_backend {
+            StorageBackend::Mongodb => {
+                let config_db = config.database();
+                let client = mongo_client(&config_db).await;
+                let database = client.database(&config_db.database_name);
+                let store = Arc::new(MongoStore::new(client, database.clone()));
+                let setup = Arc::new(DatabaseSetup::pending());
 
-        // Just check for other calls
-        let _ = env::var("DATABASE_NAME").expect("DATABASE_NAME must be set");
-
-        self.manage(client)
+                self.attach(setup::on_ignite(
+                    database,
+                    config.run_migrations,
+                    setup.clone(),
+                ))
+                .manage(setup)
+                .manage_store(store)
+            }
+            StorageBackend::Memory => {
+                tracing::warn!("using in-memory storage, data will be lost on restart");
+                self.manage(Arc::new(DatabaseSetup::done()))
+                    .manage_store(Arc::new(MemoryStore::new()))
+            }
+        }
     }
 }
 
-#[derive(Debug, Responder)]
-#[response(status = 500)]
-pub struct DatabaseErrorResponse(Json<Value>);
-impl DatabaseErrorResponse {
-    pub fn new(error: mongodb::error::Error) -> Self {
-        Self(Json(json!({
-            "message": "Database error",
-            "database_error": format!("{:?}", error)
-        })))
+/// Returns client of service database. Connection is established on first use
+pub async fn mongo_client(config: &DatabaseConfig) -> Client {
+    Client::with_uri_str(&config.database_url)
+        .await
+        .expect("Failed to create database client")
+}
+
+/// Returns service database. Connection is established on first use
+pub async fn mongo_database(config: &DatabaseConfig) -> Database {
+    mongo_client(config).await.database(&config.database_name)
+}
+
+trait RocketManageStore {
+    fn manage_store<S: CacheStore + AuditStore + WebhookStore + IdempotencyStore + 'static>(
+        self,
+        store: Arc<S>,
+    ) -> Self;
+}
+
+impl RocketManageStore for Rocket<Build> {
+    fn manage_store<S: CacheStore + AuditStore + WebhookStore + IdempotencyStore + 'static>(
+        self,
+        store: Arc<S>,
+    ) -> Self {
+        let cache_store: Arc<dyn CacheStore> = store.clone();
+        let audit_store: Arc<dyn AuditStore> = store.clone();
+        let webhook_store: Arc<dyn WebhookStore> = store.clone();
+        let idempotency_store: Arc<dyn IdempotencyStore> = store;
+        self.manage(cache_store)
+            .manage(audit_store)
+            .manage(webhook_store)
+            .manage(idempotency_store)
     }
 }
@@ -0,0 +1,788 @@
+use std::collections::HashSet;
+
+use mongodb::{
+    bson::oid::ObjectId,
+    bson::{doc, from_document, to_document, Bson, DateTime, Document},
+    error::{ErrorKind, WriteFailure},
+    options::{
+        FindOneAndUpdateOptions, FindOneOptions, FindOptions, ReplaceOptions, ReturnDocument,
+    },
+    Client, ClientSession, Collection, Database,
+};
+use rocket::{futures::TryStreamExt, tokio::sync::OnceCell};
+
+use super::{
+    cache::{short_code, text_language, Departure, Tombstone},
+    AuditEntry, AuditStore, Cache, CacheChange, CacheSort, CacheSortKey, CacheStore, CacheWrite,
+    CacheWritten, IdempotencyRecord, IdempotencyStore, LatLong, Revision, ScoredCache, StoreResult,
+    StoredResponse, WebhookDelivery, WebhookStore, WebhookSubscription,
+};
+
+/// Storage backed by MongoDB database
+pub struct MongoStore {
+    /// Client of `database`, needed to start sessions
+    client: Client,
+    database: Database,
+    collection: Collection<Cache>,
+    revisions: Collection<Revision>,
+    tombstones: Collection<Tombstone>,
+    departures: Collection<Departure>,
+    audit: Collection<AuditEntry>,
+    webhooks: Collection<WebhookSubscription>,
+    deliveries: Collection<WebhookDelivery>,
+    idempotency: Collection<IdempotencyRecord>,
+    /// Whether deployment is replica set or sharded cluster, found on first bulk write
+    transactions: OnceCell<bool>,
+}
+
+impl MongoStore {
+    pub fn new(client: Client, database: Database) -> Self {
+        Self {
+            client,
+            collection: database.collection("cache"),
+            revisions: database.collection("revisions"),
+            tombstones: database.collection("tombstones"),
+            departures: database.collection("departures"),
+            audit: database.collection("audit"),
+            webhooks: database.collection("webhooks"),
+            deliveries: database.collection("webhook_deliveries"),
+            idempotency: database.collection("idempotency"),
+            transactions: OnceCell::new(),
+            database,
+        }
+    }
+
+    /// Transactions are available on replica sets and sharded clusters only
+    async fn supports_transactions(&self) -> StoreResult<bool> {
+        let supported = self
+            .transactions
+            .get_or_try_init(|| async {
+                let hello = self.database.run_command(doc! { "hello": 1 }, None).await?;
+                Ok::<_, mongodb::error::Error>(
+                    hello.contains_key("setName") || hello.get_str("msg") == Ok("isdbgrid"),
+                )
+            })
+            .await?;
+        Ok(*supported)
+    }
+
+    /// Applies single write of `bulk_write` within session
+    async fn write_with_session(
+        &self,
+        write: CacheWrite,
+        session: &mut ClientSession,
+    ) -> StoreResult<CacheWritten> {
+        let written = match write {
+            CacheWrite::Insert(mut cache) => {
+                let inserted_id = self
+                    .collection
+                    .clone_with_type::<Document>()
+                    .insert_one_with_session(stored_document(&cache)?, None, session)
+                    .await?
+                    .inserted_id;
+                cache.id = Some(inserted_object_id(inserted_id)?);
+                CacheWritten::Inserted(cache)
+            }
+            CacheWrite::Update(cache) => {
+                let id = cache.id.expect("cannot update cache withou id");
+                let previous = self
+                    .collection
+                    .clone_with_type::<Document>()
+                    .find_one_and_update_with_session(
+                        doc! { "_id": id, "deleted_at": null },
+                        update_document(&cache),
+                        None,
+                        session,
+                    )
+                    .await?;
+
+                match previous {
+                    Some(previous) => {
+                        let revision = revision(id, previous)?;
+                        let before = revision.cache.clone();
+                        self.revisions
+                            .insert_one_with_session(revision, None, session)
+                            .await?;
+
+                        let after = self
+                            .collection
+                            .find_one_with_session(doc! { "_id": id }, None, session)
+                            .await?
+                            .expect("updated cache exists");
+                        if let Some(departure) = Departure::of(&before, &after) {
+                            self.departures
+                                .insert_one_with_session(departure, None, session)
+                                .await?;
+                        }
+                        CacheWritten::Updated { before, after }
+                    }
+                    None => CacheWritten::NotFound,
+                }
+            }
+            CacheWrite::Delete(id) => {
+                let previous = self
+                    .collection
+                    .find_one_and_update_with_session(
+                        doc! { "_id": id, "deleted_at": null },
+                        delete_document(),
+                        None,
+                        session,
+                    )
+                    .await?;
+                previous.map_or(CacheWritten::NotFound, CacheWritten::Deleted)
+            }
+        };
+        Ok(written)
+    }
+}
+
+/// Code of error inserting document with existing unique key
+const DUPLICATE_KEY: i32 = 11000;
+
+fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    matches!(
+        &*err.kind,
+        ErrorKind::Write(WriteFailure::WriteError(err)) if err.code == DUPLICATE_KEY
+    )
+}
+
+/// Id generated for inserted document. Documents of the service always get ObjectId
+fn inserted_object_id(id: Bson) -> StoreResult<ObjectId> {
+    id.as_object_id().ok_or_else(|| {
+        let message = format!("inserted document has unexpected id {}", id);
+        <mongodb::bson::de::Error as serde::de::Error>::custom(message).into()
+    })
+}
+
+/// Collection of counters increased atomically
+const COUNTERS_COLLECTION: &str = "counters";
+
+/// Counter of assigned short codes
+const CODE_COUNTER: &str = "cache_code";
+
+/// Reserves next short code. Codes are never reused, even if insert fails
+pub async fn next_code(database: &Database) -> StoreResult<String> {
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .build();
+    let counter = database
+        .collection::<Document>(COUNTERS_COLLECTION)
+        .find_one_and_update(
+            doc! { "_id": CODE_COUNTER },
+            doc! { "$inc": { "value": 1_i64 } },
+            options,
+        )
+        .await?
+        .expect("upserted counter is returned");
+
+    let value = counter.get_i64("value").expect("counter value is integer");
+    Ok(short_code(value as u64))
+}
+
+/// Filter of active caches used for lists and search
+fn list_filter(user_id: Option<i32>, bounds: Option<(LatLong, LatLong)>) -> Document {
+    let mut filter = doc! {
+        "deleted_at": null,
+    };
+    if let Some(uid) = user_id {
+        filter.insert("owner_id", uid);
+    }
+
+    if let Some((sw, ne)) = bounds {
+        filter.insert("position.lat", doc! { "$gte": sw.lat, "$lte": ne.lat });
+        filter.insert("position.lng", doc! { "$gte": sw.lng, "$lte": ne.lng });
+    }
+    filter
+}
+
+/// Projection of list item: id, position and given fields
+fn projection(fields: &[&str]) -> Document {
+    let mut projection = doc! {
+        "_id": 1,
+        "position": 1,
+    };
+    for field in fields {
+        projection.insert(*field, 1);
+    }
+    projection
+}
+
+/// Cache document as stored, with fields needed by indexes
+fn stored_document(cache: &Cache) -> StoreResult<Document> {
+    let mut document = to_document(cache)?;
+    document.insert("location", geo_point(&cache.position));
+    document.insert("language", language(cache));
+    Ok(document)
+}
+
+/// Field of cache document counting its revisions
+pub const REVISION_COUNTER: &str = "revision_count";
+
+/// Update replacing content of cache by given one. Number of the revision
+/// keeping previous content is allocated by the same update
+fn update_document(cache: &Cache) -> Document {
+    doc! {
+        "$inc": { REVISION_COUNTER: 1_i64 },
+        "$set": {
+            "name": &cache.name,
+            "position.lat": cache.position.lat,
+            "position.lng": cache.position.lng,
+            "location": geo_point(&cache.position),
+            "description": &cache.description,
+            "hint": &cache.hint,
+            "language": language(cache),
+            "updated_at": DateTime::now(),
+        },
+    }
+}
+
+/// Revision keeping cache document as it was before update by `update_document`
+fn revision(id: ObjectId, previous: Document) -> StoreResult<Revision> {
+    let number = match previous.get(REVISION_COUNTER) {
+        Some(Bson::Int64(count)) => *count as u32,
+        Some(Bson::Int32(count)) => *count as u32,
+        _ => 0,
+    };
+    Ok(Revision {
+        id: None,
+        cache_id: id,
+        revision: number + 1,
+        created_at: DateTime::now(),
+        cache: from_document(previous)?,
+    })
+}
+
+/// Update making cache soft deleted
+fn delete_document() -> Document {
+    let now = DateTime::now();
+    doc! {
+        "$set": { "deleted_at": now, "updated_at": now },
+    }
+}
+
+/// Language of cache texts kept in `language` field for text index
+fn language(cache: &Cache) -> &'static str {
+    text_language(
+        [
+            Some(cache.name.as_str()),
+            cache.description.as_deref(),
+            cache.hint.as_deref(),
+        ]
+        .into_iter()
+        .flatten(),
+    )
+}
+
+/// Tombstone of deleted cache
+fn tombstone(cache: &Cache) -> Option<Tombstone> {
+    Some(Tombstone {
+        cache_id: cache.id?,
+        position: cache.position.clone(),
+        updated_at: cache.deleted_at?,
+    })
+}
+
+/// GeoJSON point of position, kept in `location` field for geo queries
+fn geo_point(position: &LatLong) -> Document {
+    // GeoJSON keeps longitude first
+    doc! {
+        "type": "Point",
+        "coordinates": [position.lng, position.lat],
+    }
+}
+
+#[async_trait]
+impl CacheStore for MongoStore {
+    async fn insert_cache(&self, mut cache: Cache) -> StoreResult<Cache> {
+        cache.code = Some(next_code(&self.database).await?);
+
+        let document = stored_document(&cache)?;
+
+        let inserted_id = self
+            .collection
+            .clone_with_type::<Document>()
+            .insert_one(document, None)
+            .await?
+            .inserted_id;
+        cache.id = Some(inserted_object_id(inserted_id)?);
+        Ok(cache)
+    }
+
+    async fn get_caches(
+        &self,
+        user_id: Option<i32>,
+        bounds: Option<(LatLong, LatLong)>,
+        fields: &[&str],
+        sort: Option<&CacheSort>,
+    ) -> StoreResult<Vec<Cache>> {
+        let filter = list_filter(user_id, bounds);
+        let direction = match sort {
+            Some(sort) if sort.descending => -1,
+            _ => 1,
+        };
+
+        let sort = match sort.map(|sort| &sort.key) {
+            None => None,
+            Some(CacheSortKey::CreatedAt) => {
+                Some(doc! { "created_at": direction, "_id": direction })
+            }
+            Some(CacheSortKey::UpdatedAt) => {
+                Some(doc! { "updated_at": direction, "_id": direction })
+            }
+            Some(CacheSortKey::Distance(point)) => {
+                // Distance is known only inside aggregation
+                let pipeline = [
+                    doc! {
+                        "$geoNear": {
+                            "near": geo_point(point),
+                            "distanceField": "distance",
+                            "spherical": true,
+                            "query": filter,
+                        },
+                    },
+                    doc! { "$sort": { "distance": direction, "_id": direction } },
+                    doc! { "$project": projection(fields) },
+                ];
+                let cursor = self.collection.aggregate(pipeline, None).await?;
+                return cursor
+                    .and_then(|document| async move { Ok(from_document(document)?) })
+                    .try_collect()
+                    .await;
+            }
+        };
+
+        let options = FindOptions::builder()
+            .projection(projection(fields))
+            .sort(sort)
+            .build();
+
+        let cursor = self.collection.find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    async fn search_caches(
+        &self,
+        text: &str,
+        user_id: Option<i32>,
+        bounds: Option<(LatLong, LatLong)>,
+        fields: &[&str],
+    ) -> StoreResult<Vec<ScoredCache>> {
+        let mut filter = list_filter(user_id, bounds);
+        filter.insert(
+            "$text",
+            doc! { "$search": text, "$language": text_language([text]) },
+        );
+
+        let mut projection = projection(fields);
+        projection.insert("score", doc! { "$meta": "textScore" });
+        let options = FindOptions::builder()
+            .projection(projection)
+            .sort(doc! { "score": { "$meta": "textScore" }, "_id": 1 })
+            .build();
+
+        let cursor = self
+            .collection
+            .clone_with_type::<ScoredCache>()
+            .find(filter, options)
+            .await?;
+        cursor.try_collect().await
+    }
+
+    async fn get_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>> {
+        let filter = doc! {
+            "_id": id,
+            "deleted_at": null,
+        };
+
+        self.collection.find_one(filter, None).await
+    }
+
+    async fn get_caches_by_ids(
+        &self,
+        ids: &[ObjectId],
+        codes: &[String],
+    ) -> StoreResult<Vec<Cache>> {
+        let filter = doc! {
+            "deleted_at": null,
+            "$or": [
+                { "_id": { "$in": ids } },
+                { "code": { "$in": codes } },
+            ],
+        };
+
+        let cursor = self.collection.find(filter, None).await?;
+        cursor.try_collect().await
+    }
+
+    async fn get_cache_id_by_code(&self, code: &str) -> StoreResult<Option<ObjectId>> {
+        let options = FindOneOptions::builder()
+            .projection(doc! { "_id": 1 })
+            .build();
+        let cache = self
+            .collection
+            .clone_with_type::<Document>()
+            .find_one(doc! { "code": code }, options)
+            .await?;
+        Ok(cache.and_then(|cache| cache.get_object_id("_id").ok()))
+    }
+
+    async fn get_any_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>> {
+        let filter = doc! {
+            "_id": id,
+        };
+
+        self.collection.find_one(filter, None).await
+    }
+
+    async fn update_cache(&self, cache: Cache) -> StoreResult<Option<Cache>> {
+        // Update and its revision share transaction where deployment has them.
+        // On standalone server failure between them leaves revision number unused
+        let mut session = self.client.start_session(None).await?;
+        let transaction = self.supports_transactions().await?;
+        if transaction {
+            session.start_transaction(None).await?;
+        }
+
+        let written = self
+            .write_with_session(CacheWrite::Update(cache), &mut session)
+            .await;
+        if transaction {
+            match &written {
+                Ok(_) => session.commit_transaction().await?,
+                // Error of write is more useful than error of abort
+                Err(_) => session.abort_transaction().await.unwrap_or_default(),
+            }
+        }
+
+        match written? {
+            CacheWritten::Updated { before, .. } => Ok(Some(before)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn bulk_write(&self, mut writes: Vec<CacheWrite>) -> StoreResult<Vec<CacheWritten>> {
+        // Codes are reserved before transaction, so concurrent creations
+        // do not conflict on the counter
+        for write in &mut writes {
+            if let CacheWrite::Insert(cache) = write {
+                cache.code = Some(next_code(&self.database).await?);
+            }
+        }
+
+        let mut session = self.client.start_session(None).await?;
+        let mut written = Vec::with_capacity(writes.len());
+
+        // Without transactions applied writes stay, so each failure is
+        // reported in place of its write and the rest are still applied
+        if !self.supports_transactions().await? {
+            for write in writes {
+                match self.write_with_session(write, &mut session).await {
+                    Ok(result) => written.push(result),
+                    Err(err) => {
+                        tracing::error!(error = %err, "bulk cache write failed");
+                        written.push(CacheWritten::Failed);
+                    }
+                }
+            }
+            return Ok(written);
+        }
+
+        session.start_transaction(None).await?;
+        for write in writes {
+            match self.write_with_session(write, &mut session).await {
+                Ok(result) => written.push(result),
+                Err(err) => {
+                    // Error of write is more useful than error of abort
+                    session.abort_transaction().await.unwrap_or_default();
+                    return Err(err);
+                }
+            }
+        }
+        session.commit_transaction().await?;
+        Ok(written)
+    }
+
+    async fn get_revisions(&self, id: ObjectId) -> StoreResult<Vec<Revision>> {
+        let filter = doc! {
+            "cache_id": id,
+        };
+
+        let options = FindOptions::builder().sort(doc! { "revision": 1 }).build();
+
+        let cursor = self.revisions.find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    async fn get_revision(&self, id: ObjectId, number: u32) -> StoreResult<Option<Revision>> {
+        let filter = doc! {
+            "cache_id": id,
+            "revision": number,
+        };
+
+        self.revisions.find_one(filter, None).await
+    }
+
+    async fn delete_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>> {
+        let filter = doc! {
+            "_id": id,
+            "deleted_at": null,
+        };
+        self.collection
+            .find_one_and_update(filter, delete_document(), None)
+            .await
+    }
+
+    async fn restore_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>> {
+        let filter = doc! {
+            "_id": id,
+            "deleted_at": { "$ne": null },
+        };
+        let update = doc! {
+            "$unset": { "deleted_at": "" },
+            "$set": { "updated_at": DateTime::now() },
+        };
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        self.collection
+            .find_one_and_update(filter, update, options)
+            .await
+    }
+
+    async fn purge_deleted(&self, deleted_before: DateTime) -> StoreResult<u64> {
+        let filter = doc! {
+            "deleted_at": { "$lt": deleted_before },
+        };
+        let options = FindOptions::builder()
+            .projection(doc! { "_id": 1, "position": 1, "deleted_at": 1 })
+            .build();
+        let purged: Vec<Cache> = self
+            .collection
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?;
+        if purged.is_empty() {
+            return Ok(0);
+        }
+
+        // Tombstone is written first, so purge interrupted after removal of
+        // cache does not lose it. Caches restored since they were found are kept
+        let options = ReplaceOptions::builder().upsert(true).build();
+        let mut removed = 0;
+        for tombstone in purged.iter().filter_map(tombstone) {
+            let id = tombstone.cache_id;
+            self.tombstones
+                .replace_one(doc! { "_id": id }, &tombstone, options.clone())
+                .await?;
+
+            let filter = doc! {
+                "_id": id,
+                "deleted_at": { "$lt": deleted_before },
+            };
+            let deleted = self.collection.delete_one(filter, None).await?;
+            if deleted.deleted_count == 0 {
+                self.tombstones.delete_one(doc! { "_id": id }, None).await?;
+                continue;
+            }
+
+            self.revisions
+                .delete_many(doc! { "cache_id": id }, None)
+                .await?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    async fn get_changes(
+        &self,
+        since: Option<DateTime>,
+        bounds: Option<(LatLong, LatLong)>,
+    ) -> StoreResult<Vec<CacheChange>> {
+        let mut filter = match since {
+            Some(since) => doc! { "updated_at": { "$gt": since } },
+            None => doc! { "deleted_at": null },
+        };
+        if let Some((sw, ne)) = bounds {
+            filter.insert("position.lat", doc! { "$gte": sw.lat, "$lte": ne.lat });
+            filter.insert("position.lng", doc! { "$gte": sw.lng, "$lte": ne.lng });
+        }
+
+        let options = FindOptions::builder()
+            .projection(doc! { "_id": 1, "position": 1, "deleted_at": 1 })
+            .build();
+        let mut changes: Vec<CacheChange> = self
+            .collection
+            .find(filter.clone(), options)
+            .await?
+            .map_ok(|cache| CacheChange {
+                id: cache.id.expect("stored cache has id"),
+                deleted: cache.deleted_at.is_some(),
+            })
+            .try_collect()
+            .await?;
+
+        if since.is_some() {
+            let tombstones: Vec<Tombstone> = self
+                .tombstones
+                .find(filter.clone(), None)
+                .await?
+                .try_collect()
+                .await?;
+            changes.extend(tombstones.into_iter().map(|t| CacheChange {
+                id: t.cache_id,
+                deleted: true,
+            }));
+
+            // Caches which moved within area or back into it are reported as they are now
+            let departures: Vec<Departure> = self
+                .departures
+                .find(filter, None)
+                .await?
+                .try_collect()
+                .await?;
+            let reported: HashSet<ObjectId> = changes.iter().map(|c| c.id).collect();
+            let departed: HashSet<ObjectId> = departures
+                .into_iter()
+                .map(|d| d.cache_id)
+                .filter(|id| !reported.contains(id))
+                .collect();
+            changes.extend(
+                departed
+                    .into_iter()
+                    .map(|id| CacheChange { id, deleted: true }),
+            );
+        }
+
+        Ok(changes)
+    }
+
+    async fn ping(&self) -> StoreResult<()> {
+        self.database
+            .run_command(doc! { "ping": 1 }, None)
+            .await
+            .map(|_| ())
+    }
+}
+
+#[async_trait]
+impl AuditStore for MongoStore {
+    async fn record(&self, entry: AuditEntry) -> StoreResult<()> {
+        self.audit.insert_one(entry, None).await.map(|_| ())
+    }
+
+    async fn get_history(&self, cache_id: ObjectId) -> StoreResult<Vec<AuditEntry>> {
+        let filter = doc! {
+            "cache_id": cache_id,
+        };
+
+        let options = FindOptions::builder()
+            .sort(doc! { "timestamp": 1, "_id": 1 })
+            .build();
+
+        let cursor = self.audit.find(filter, options).await?;
+        cursor.try_collect().await
+    }
+}
+
+#[async_trait]
+impl WebhookStore for MongoStore {
+    async fn insert_subscription(
+        &self,
+        subscription: WebhookSubscription,
+    ) -> StoreResult<ObjectId> {
+        let inserted_id = self
+            .webhooks
+            .insert_one(subscription, None)
+            .await?
+            .inserted_id;
+        inserted_object_id(inserted_id)
+    }
+
+    async fn get_subscriptions(&self) -> StoreResult<Vec<WebhookSubscription>> {
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+        let cursor = self.webhooks.find(None, options).await?;
+        cursor.try_collect().await
+    }
+
+    async fn delete_subscription(&self, id: ObjectId) -> StoreResult<bool> {
+        let deleted = self.webhooks.delete_one(doc! { "_id": id }, None).await?;
+        self.deliveries
+            .delete_many(doc! { "subscription_id": id }, None)
+            .await?;
+        Ok(deleted.deleted_count > 0)
+    }
+
+    async fn record_delivery(&self, delivery: WebhookDelivery) -> StoreResult<()> {
+        self.deliveries.insert_one(delivery, None).await.map(|_| ())
+    }
+
+    async fn get_deliveries(
+        &self,
+        subscription_id: ObjectId,
+        limit: i64,
+    ) -> StoreResult<Vec<WebhookDelivery>> {
+        let filter = doc! {
+            "subscription_id": subscription_id,
+        };
+
+        let options = FindOptions::builder()
+            .sort(doc! { "timestamp": -1, "_id": -1 })
+            .limit(limit)
+            .build();
+
+        let cursor = self.deliveries.find(filter, options).await?;
+        cursor.try_collect().await
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MongoStore {
+    async fn reserve_key(
+        &self,
+        record: IdempotencyRecord,
+    ) -> StoreResult<Option<IdempotencyRecord>> {
+        // MongoDB removes expired records about once a minute, so they may still exist
+        let expired = doc! {
+            "_id": &record.id,
+            "expires_at": { "$lte": DateTime::now() },
+        };
+        self.idempotency.delete_one(expired, None).await?;
+
+        match self.idempotency.insert_one(&record, None).await {
+            Ok(_) => Ok(None),
+            Err(err) if is_duplicate_key(&err) => {
+                self.idempotency
+                    .find_one(doc! { "_id": &record.id }, None)
+                    .await
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn complete_key(
+        &self,
+        id: &str,
+        response: StoredResponse,
+        expires_at: DateTime,
+    ) -> StoreResult<()> {
+        let update = doc! {
+            "$set": {
+                "response": { "status": response.status as i32, "body": response.body },
+                "expires_at": expires_at,
+            },
+        };
+        self.idempotency
+            .update_one(doc! { "_id": id }, update, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn release_key(&self, id: &str) -> StoreResult<()> {
+        self.idempotency
+            .delete_one(doc! { "_id": id }, None)
+            .await?;
+        Ok(())
+    }
+}
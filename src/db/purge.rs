@@ -0,0 +1,50 @@
+use std::{sync::Arc, time::Duration};
+
+use mongodb::bson::DateTime;
+use rocket::{fairing::AdHoc, tokio, Build, Rocket};
+
+use super::CacheStore;
+use crate::config::Config;
+
+/// How often soft deleted caches are checked for removal
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub trait RocketSchedulePurge {
+    fn schedule_purge(self, config: &Config) -> Self;
+}
+
+impl RocketSchedulePurge for Rocket<Build> {
+    /// Starts background task removing soft deleted caches
+    /// older than configured retention period
+    fn schedule_purge(self, config: &Config) -> Self {
+        let retention = config.deleted_retention();
+
+        self.attach(AdHoc::on_liftoff("Deleted caches purge", move |rocket| {
+            Box::pin(async move {
+                let store = rocket
+                    .state::<Arc<dyn CacheStore>>()
+                    .expect("Database must be connected before purge scheduling")
+                    .clone();
+
+                tokio::spawn(purge_loop(store, retention));
+            })
+        }))
+    }
+}
+
+async fn purge_loop(store: Arc<dyn CacheStore>, retention: Duration) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let deleted_before = DateTime::from_millis(
+            DateTime::now().timestamp_millis() - retention.as_millis() as i64,
+        );
+        match store.purge_deleted(deleted_before).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!(count, "purged deleted caches"),
+            Err(err) => tracing::error!(error = %err, "failed to purge deleted caches"),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use super::Cache;
+
+/// Snapshot of cache before one of its updates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+
+    pub cache_id: ObjectId,
+    /// Sequential number of the revision starting from 1
+    pub revision: u32,
+    /// Time when snapshot was replaced by newer version
+    pub created_at: DateTime,
+    pub cache: Cache,
+}
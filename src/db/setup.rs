@@ -0,0 +1,182 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use mongodb::{
+    error::{Error, ErrorKind},
+    Database,
+};
+use rocket::{fairing::AdHoc, futures::future::BoxFuture, tokio};
+
+use super::{indexes::ensure_indexes, migrations::run_migrations};
+
+/// Delay before first retry of failed setup, doubled after each failure
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Progress of preparing database for requests. Service is not ready until
+/// setup is done, but it starts and answers health checks meanwhile
+#[derive(Debug)]
+pub struct DatabaseSetup(Mutex<SetupState>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetupState {
+    Pending,
+    /// Database was unreachable, next attempt is scheduled
+    Failed,
+    /// Existing data or indexes conflict with setup, which is not retried
+    Conflict,
+    Done,
+}
+
+impl DatabaseSetup {
+    pub fn pending() -> Self {
+        Self(Mutex::new(SetupState::Pending))
+    }
+
+    /// Setup of storage which needs none
+    pub fn done() -> Self {
+        Self(Mutex::new(SetupState::Done))
+    }
+
+    /// Returns why database is not prepared yet
+    pub fn check(&self) -> Result<(), &'static str> {
+        match *self.0.lock().unwrap() {
+            SetupState::Pending => Err("pending"),
+            SetupState::Failed => Err("failed"),
+            SetupState::Conflict => Err("conflict"),
+            SetupState::Done => Ok(()),
+        }
+    }
+
+    fn set(&self, state: SetupState) {
+        *self.0.lock().unwrap() = state;
+    }
+}
+
+/// Failed step of database setup
+#[derive(Debug)]
+pub enum SetupError {
+    /// Database could not be reached. Setup succeeds once it is back
+    Unavailable(String),
+    /// Step was rejected by database, like unique index over repeated
+    /// values. It fails until data is fixed
+    Conflict(String),
+}
+
+impl SetupError {
+    /// Sorts error of step described by `context`
+    pub fn new(context: String, err: Error) -> Self {
+        let message = format!("{}: {}", context, err);
+        if is_unavailable(&err) {
+            Self::Unavailable(message)
+        } else {
+            Self::Conflict(message)
+        }
+    }
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unavailable(message) | Self::Conflict(message) => f.write_str(message),
+        }
+    }
+}
+
+/// Errors of connection and server selection, which go away once database is back
+fn is_unavailable(err: &Error) -> bool {
+    matches!(
+        *err.kind,
+        ErrorKind::ServerSelection { .. }
+            | ErrorKind::Io(_)
+            | ErrorKind::ConnectionPoolCleared { .. }
+            | ErrorKind::DnsResolve { .. }
+    )
+}
+
+/// Fairing which applies pending migrations, if `migrate` is set, and then
+/// creates missing indexes. Launch fails on conflict with existing data.
+/// If database is unreachable, service starts and setup is retried in
+/// background until database is back
+pub fn on_ignite(database: Database, migrate: bool, setup: Arc<DatabaseSetup>) -> AdHoc {
+    AdHoc::try_on_ignite("MongoDB setup", move |rocket| -> BoxFuture<'_, _> {
+        Box::pin(async move {
+            match prepare(&database, migrate).await {
+                Ok(()) => {
+                    setup.set(SetupState::Done);
+                    Ok(rocket)
+                }
+                Err(err @ SetupError::Conflict(_)) => {
+                    tracing::error!(error = %err, "database setup failed, fix data and restart");
+                    Err(rocket)
+                }
+                Err(err @ SetupError::Unavailable(_)) => {
+                    setup.set(SetupState::Failed);
+                    tracing::error!(
+                        error = %err,
+                        retry_in_secs = FIRST_RETRY_DELAY.as_secs(),
+                        "database setup failed"
+                    );
+                    tokio::spawn(setup_loop(database, migrate, setup));
+                    Ok(rocket)
+                }
+            }
+        })
+    })
+}
+
+async fn setup_loop(database: Database, migrate: bool, setup: Arc<DatabaseSetup>) {
+    let mut delay = FIRST_RETRY_DELAY;
+
+    loop {
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+
+        match prepare(&database, migrate).await {
+            Ok(()) => {
+                setup.set(SetupState::Done);
+                return;
+            }
+            // Service is already running, so it stays unready until restarted
+            Err(err @ SetupError::Conflict(_)) => {
+                setup.set(SetupState::Conflict);
+                tracing::error!(error = %err, "database setup failed, fix data and restart");
+                return;
+            }
+            Err(err @ SetupError::Unavailable(_)) => {
+                setup.set(SetupState::Failed);
+                tracing::error!(
+                    error = %err,
+                    retry_in_secs = delay.as_secs(),
+                    "database setup failed"
+                );
+            }
+        }
+    }
+}
+
+/// Migrations go first, so indexes see migrated documents. Migration
+/// interrupted by failure is applied again, as steps are idempotent
+async fn prepare(database: &Database, migrate: bool) -> Result<(), SetupError> {
+    if migrate {
+        let results = run_migrations(database, false)
+            .await
+            .map_err(|err| SetupError::new("database migration failed".to_string(), err))?;
+        for result in results {
+            tracing::info!(
+                migration = result.name,
+                documents = result.documents,
+                "applied database migration"
+            );
+        }
+    }
+
+    for index in ensure_indexes(database).await? {
+        tracing::info!(index = %index, "created database index");
+    }
+    tracing::info!("database setup finished");
+    Ok(())
+}
@@ -0,0 +1,268 @@
+use std::{ops::Deref, sync::Arc};
+
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rocket::{
+    request::{FromRequest, Outcome},
+    Request, State,
+};
+
+use super::{
+    AuditEntry, Cache, CacheChange, CacheSort, IdempotencyRecord, LatLong, Revision, ScoredCache,
+    StoredResponse, WebhookDelivery, WebhookSubscription,
+};
+
+/// Storage errors. In-memory storage never fails
+pub type StoreResult<T> = Result<T, mongodb::error::Error>;
+
+/// Change of cache made by `CacheStore::bulk_write`
+#[derive(Debug, Clone)]
+pub enum CacheWrite {
+    Insert(Cache),
+    /// Replaces content of cache having id of given one
+    Update(Cache),
+    Delete(ObjectId),
+}
+
+/// Result of `CacheWrite` in the same position
+#[derive(Debug, Clone)]
+pub enum CacheWritten {
+    Inserted(Cache),
+    Updated {
+        before: Cache,
+        after: Cache,
+    },
+    /// Cache as it was before deletion
+    Deleted(Cache),
+    /// Cache does not exist or is deleted
+    NotFound,
+    /// Storage failed to apply the write. Only reported where writes
+    /// are not applied in transaction
+    Failed,
+}
+
+/// Storage of caches and their revisions
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    /// Stores new cache under new short code. Returns stored cache with id and code
+    async fn insert_cache(&self, cache: Cache) -> StoreResult<Cache>;
+
+    /// Return active caches with id, position and given `fields` only.
+    /// Without `sort` caches are returned in storage order
+    async fn get_caches(
+        &self,
+        user_id: Option<i32>,
+        bounds: Option<(LatLong, LatLong)>,
+        fields: &[&str],
+        sort: Option<&CacheSort>,
+    ) -> StoreResult<Vec<Cache>>;
+
+    /// Same as `get_caches` but only caches matching text, most relevant first
+    async fn search_caches(
+        &self,
+        text: &str,
+        user_id: Option<i32>,
+        bounds: Option<(LatLong, LatLong)>,
+        fields: &[&str],
+    ) -> StoreResult<Vec<ScoredCache>>;
+
+    async fn get_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>>;
+
+    /// Returns active caches having any of given ids or short codes
+    async fn get_caches_by_ids(
+        &self,
+        ids: &[ObjectId],
+        codes: &[String],
+    ) -> StoreResult<Vec<Cache>>;
+
+    /// Returns id of cache with short code, including soft deleted one
+    async fn get_cache_id_by_code(&self, code: &str) -> StoreResult<Option<ObjectId>>;
+
+    /// Same as `get_cache_by_id` but also returns soft deleted cache
+    async fn get_any_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>>;
+
+    /// Updates cache and keeps its previous version as new revision.
+    /// Returns previous version of cache if it exists
+    async fn update_cache(&self, cache: Cache) -> StoreResult<Option<Cache>>;
+
+    /// Applies writes in order like `insert_cache`, `update_cache` and
+    /// `delete_cache_by_id` do. Writes are applied all or none where storage
+    /// supports transactions. Otherwise each write is applied separately and
+    /// failed ones are returned as `CacheWritten::Failed`
+    async fn bulk_write(&self, writes: Vec<CacheWrite>) -> StoreResult<Vec<CacheWritten>>;
+
+    /// Returns all previous versions of cache from oldest to newest
+    async fn get_revisions(&self, id: ObjectId) -> StoreResult<Vec<Revision>>;
+
+    async fn get_revision(&self, id: ObjectId, number: u32) -> StoreResult<Option<Revision>>;
+
+    /// Marks cache as deleted. Returns cache as it was before deletion if it exists
+    async fn delete_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>>;
+
+    /// Returns soft deleted cache back. Returns restored cache if it was deleted
+    async fn restore_cache_by_id(&self, id: ObjectId) -> StoreResult<Option<Cache>>;
+
+    /// Removes caches deleted before `deleted_before` together with their revisions.
+    /// Tombstones of removed caches are kept for `TOMBSTONE_RETENTION`.
+    /// Returns count of removed caches
+    async fn purge_deleted(&self, deleted_before: DateTime) -> StoreResult<u64>;
+
+    /// Returns caches in bounds changed after `since`, including deleted ones.
+    /// Without `since` returns all active caches in bounds
+    async fn get_changes(
+        &self,
+        since: Option<DateTime>,
+        bounds: Option<(LatLong, LatLong)>,
+    ) -> StoreResult<Vec<CacheChange>>;
+
+    /// Checks that storage is available
+    async fn ping(&self) -> StoreResult<()>;
+}
+
+/// Append-only storage of cache changes
+#[async_trait]
+pub trait AuditStore: Send + Sync {
+    async fn record(&self, entry: AuditEntry) -> StoreResult<()>;
+
+    /// Returns all changes of cache from oldest to newest
+    async fn get_history(&self, cache_id: ObjectId) -> StoreResult<Vec<AuditEntry>>;
+}
+
+/// Storage of webhook subscriptions and their delivery log
+#[async_trait]
+pub trait WebhookStore: Send + Sync {
+    async fn insert_subscription(&self, subscription: WebhookSubscription)
+        -> StoreResult<ObjectId>;
+
+    async fn get_subscriptions(&self) -> StoreResult<Vec<WebhookSubscription>>;
+
+    /// Removes subscription with its delivery log. Returns if it existed
+    async fn delete_subscription(&self, id: ObjectId) -> StoreResult<bool>;
+
+    async fn record_delivery(&self, delivery: WebhookDelivery) -> StoreResult<()>;
+
+    /// Returns latest delivery attempts of subscription, newest first
+    async fn get_deliveries(
+        &self,
+        subscription_id: ObjectId,
+        limit: i64,
+    ) -> StoreResult<Vec<WebhookDelivery>>;
+}
+
+/// Storage of requests made with idempotency keys
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Saves record unless unexpired record with the same id exists.
+    /// Returns the existing record in that case
+    async fn reserve_key(
+        &self,
+        record: IdempotencyRecord,
+    ) -> StoreResult<Option<IdempotencyRecord>>;
+
+    /// Saves response of request which reserved key and extends its record
+    /// until `expires_at`
+    async fn complete_key(
+        &self,
+        id: &str,
+        response: StoredResponse,
+        expires_at: DateTime,
+    ) -> StoreResult<()>;
+
+    /// Removes record of failed request, so it may be retried
+    async fn release_key(&self, id: &str) -> StoreResult<()>;
+}
+
+/// Cache storage selected by configuration
+pub struct CacheDatabase(Arc<dyn CacheStore>);
+
+impl Deref for CacheDatabase {
+    type Target = dyn CacheStore;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for CacheDatabase {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let store = req
+            .guard::<&State<Arc<dyn CacheStore>>>()
+            .await
+            .expect("Storage must be added to rocket");
+        Outcome::Success(Self(Arc::clone(store)))
+    }
+}
+
+/// Audit storage selected by configuration
+pub struct AuditDatabase(Arc<dyn AuditStore>);
+
+impl Deref for AuditDatabase {
+    type Target = dyn AuditStore;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for AuditDatabase {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let store = req
+            .guard::<&State<Arc<dyn AuditStore>>>()
+            .await
+            .expect("Storage must be added to rocket");
+        Outcome::Success(Self(Arc::clone(store)))
+    }
+}
+
+/// Webhook storage selected by configuration
+pub struct WebhookDatabase(Arc<dyn WebhookStore>);
+
+impl Deref for WebhookDatabase {
+    type Target = dyn WebhookStore;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for WebhookDatabase {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let store = req
+            .guard::<&State<Arc<dyn WebhookStore>>>()
+            .await
+            .expect("Storage must be added to rocket");
+        Outcome::Success(Self(Arc::clone(store)))
+    }
+}
+
+/// Idempotency storage selected by configuration
+pub struct IdempotencyDatabase(Arc<dyn IdempotencyStore>);
+
+impl Deref for IdempotencyDatabase {
+    type Target = dyn IdempotencyStore;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for IdempotencyDatabase {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let store = req
+            .guard::<&State<Arc<dyn IdempotencyStore>>>()
+            .await
+            .expect("Storage must be added to rocket");
+        Outcome::Success(Self(Arc::clone(store)))
+    }
+}
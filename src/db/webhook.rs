@@ -0,0 +1,39 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::events::CacheEventKind;
+
+/// Endpoint receiving signed notifications about cache changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub url: String,
+    /// Key of HMAC-SHA256 signature of payloads
+    pub secret: String,
+    /// Kinds of events sent to the endpoint
+    pub events: Vec<CacheEventKind>,
+    /// Moderator who created subscription
+    pub created_by: i32,
+    pub created_at: DateTime,
+}
+
+/// Single attempt to deliver event to subscription
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub subscription_id: ObjectId,
+    /// Id of event, the same for all attempts
+    pub event_id: ObjectId,
+    pub event: CacheEventKind,
+    /// Attempt number starting from 1
+    pub attempt: u32,
+    pub success: bool,
+    /// Status of endpoint response if it answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub timestamp: DateTime,
+}
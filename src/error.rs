@@ -0,0 +1,55 @@
+use rocket::{
+    http::Status,
+    response::{self, Responder},
+    Request,
+};
+
+use crate::{metrics, request_id::RequestId, status::Problem};
+
+/// Errors of API handlers. Details useful for clients only are sent,
+/// everything else is logged under request id
+#[derive(Debug)]
+pub enum ApiError {
+    WrongObjectID,
+    BadRequest(String),
+    Forbidden(String),
+    NotFound(String),
+    /// Request conflicts with another one being processed
+    Conflict(String),
+    /// Request is well-formed but cannot be applied
+    Unprocessable(String),
+    /// Resource existed but is no longer available
+    Gone(String),
+    DBError(mongodb::error::Error),
+}
+
+impl From<mongodb::error::Error> for ApiError {
+    fn from(err: mongodb::error::Error) -> Self {
+        Self::DBError(err)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let (status, detail) = match self {
+            ApiError::WrongObjectID => (Status::BadRequest, "Wrong ObjectID format".to_string()),
+            ApiError::BadRequest(detail) => (Status::BadRequest, detail),
+            ApiError::Forbidden(detail) => (Status::Forbidden, detail),
+            ApiError::NotFound(detail) => (Status::NotFound, detail),
+            ApiError::Conflict(detail) => (Status::Conflict, detail),
+            ApiError::Unprocessable(detail) => (Status::UnprocessableEntity, detail),
+            ApiError::Gone(detail) => (Status::Gone, detail),
+            ApiError::DBError(err) => {
+                metrics::record_database_error();
+                tracing::error!(
+                    request_id = %RequestId::of(req).0,
+                    error = ?err,
+                    "database error"
+                );
+                (Status::InternalServerError, "Database error".to_string())
+            }
+        };
+
+        Problem::new(req, status, Some(detail)).respond_to(req)
+    }
+}
@@ -0,0 +1,189 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rocket::{
+    request::{FromRequest, Outcome},
+    tokio::sync::{broadcast, mpsc},
+    Build, Request, Rocket,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{Cache, LatLong};
+
+/// Count of latest events kept for clients reconnecting with `Last-Event-ID`
+const REPLAY_WINDOW: usize = 1000;
+
+/// Events not yet received by slow subscriber before it is disconnected
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Kind of cache change visible on the map. The service keeps no visit
+/// logs of caches, so there are no events about them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheEventKind {
+    /// Cache created or restored after deletion
+    Created,
+    /// Cache edited or reverted to previous revision
+    Updated,
+    Deleted,
+}
+
+impl CacheEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheEventKind::Created => "created",
+            CacheEventKind::Updated => "updated",
+            CacheEventKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// Change of a cache published to subscribers
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheEvent {
+    /// Sequential number of event, used as SSE id. Restarts with process
+    #[serde(skip)]
+    pub id: u64,
+    /// Id unique across processes and restarts, sent to webhooks
+    #[serde(skip)]
+    pub uid: ObjectId,
+    /// Time of the change
+    #[serde(skip)]
+    pub timestamp: DateTime,
+    #[serde(rename = "type")]
+    pub kind: CacheEventKind,
+    pub cache_id: ObjectId,
+    pub position: LatLong,
+    /// Position before update, so subscribers see caches leaving their area
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_position: Option<LatLong>,
+}
+
+impl CacheEvent {
+    /// Checks if event concerns area between south-west and north-east corners
+    pub fn is_within(&self, (sw, ne): &(LatLong, LatLong)) -> bool {
+        let contains =
+            |p: &LatLong| (sw.lat..=ne.lat).contains(&p.lat) && (sw.lng..=ne.lng).contains(&p.lng);
+        contains(&self.position) || self.previous_position.as_ref().is_some_and(contains)
+    }
+}
+
+struct History {
+    next_id: u64,
+    events: VecDeque<CacheEvent>,
+    /// Receivers of all events, see `EventBus::queue`
+    queues: Vec<mpsc::UnboundedSender<CacheEvent>>,
+}
+
+/// In-process channel of cache changes. Events of other service instances are not seen
+pub struct EventBus {
+    sender: broadcast::Sender<CacheEvent>,
+    history: Mutex<History>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            sender,
+            history: Mutex::new(History {
+                next_id: 1,
+                events: VecDeque::with_capacity(REPLAY_WINDOW),
+                queues: Vec::new(),
+            }),
+        }
+    }
+
+    /// Publishes change of cache. `previous` is cache before the change if it existed
+    pub fn publish(&self, kind: CacheEventKind, cache: &Cache, previous: Option<&Cache>) {
+        let Some(cache_id) = cache.id else {
+            return;
+        };
+
+        // Sending under lock keeps history and live events in the same order
+        let mut history = self.history.lock().unwrap();
+        let event = CacheEvent {
+            id: history.next_id,
+            uid: ObjectId::new(),
+            timestamp: DateTime::now(),
+            kind,
+            cache_id,
+            position: cache.position.clone(),
+            previous_position: previous
+                .map(|p| p.position.clone())
+                .filter(|p| p.lat != cache.position.lat || p.lng != cache.position.lng),
+        };
+        history.next_id += 1;
+
+        if history.events.len() == REPLAY_WINDOW {
+            history.events.pop_front();
+        }
+        history.events.push_back(event.clone());
+        history
+            .queues
+            .retain(|queue| queue.send(event.clone()).is_ok());
+
+        // No subscribers is not an error
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribes to new events. Events after `last_event_id` still kept
+    /// in replay window are returned to be sent first
+    pub fn subscribe(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> (Vec<CacheEvent>, broadcast::Receiver<CacheEvent>) {
+        let history = self.history.lock().unwrap();
+        let replay = match last_event_id {
+            Some(last) => history
+                .events
+                .iter()
+                .filter(|event| event.id > last)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        (replay, self.sender.subscribe())
+    }
+
+    /// Subscribes to all new events. Unlike `subscribe` no event is dropped
+    /// for slow receiver, so it is meant for background consumers which
+    /// keep up on average
+    pub fn queue(&self) -> mpsc::UnboundedReceiver<CacheEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.history.lock().unwrap().queues.push(sender);
+        receiver
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Id of the last event received by reconnecting SSE client
+pub struct LastEventId(pub Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = req
+            .headers()
+            .get_one("Last-Event-ID")
+            .and_then(|id| id.trim().parse().ok());
+        Outcome::Success(LastEventId(id))
+    }
+}
+
+pub trait RocketEventsAdd {
+    fn events_add(self) -> Self;
+}
+
+impl RocketEventsAdd for Rocket<Build> {
+    fn events_add(self) -> Self {
+        self.manage(EventBus::new())
+    }
+}
@@ -0,0 +1,113 @@
+use std::{sync::Arc, time::Duration};
+
+use rocket::{
+    http::Status,
+    serde::json::Json,
+    tokio::{join, time::timeout},
+    Build, Rocket, State,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    db::{CacheStore, DatabaseSetup},
+    login_service::LoginService,
+};
+
+/// Maximum time given to each dependency to respond
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub trait RocketHealthAdd {
+    fn health_add(self) -> Self;
+}
+
+impl RocketHealthAdd for Rocket<Build> {
+    fn health_add(self) -> Self {
+        self.mount("/", routes![health, ready])
+    }
+}
+
+/// State of one dependency
+#[derive(Debug, Serialize)]
+pub struct DependencyCheck {
+    ready: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl DependencyCheck {
+    fn from_result<E>(result: Result<Result<(), E>, rocket::tokio::time::error::Elapsed>) -> Self {
+        let error = match result {
+            Ok(Ok(_)) => None,
+            Ok(Err(_)) => Some("unreachable".to_string()),
+            Err(_) => Some("timeout".to_string()),
+        };
+
+        Self {
+            ready: error.is_none(),
+            error,
+        }
+    }
+
+    fn from_setup(setup: &DatabaseSetup) -> Self {
+        let error = setup.check().err().map(str::to_string);
+        Self {
+            ready: error.is_none(),
+            error,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    ready: bool,
+    database: DependencyCheck,
+    /// Indexes and migrations required by requests
+    database_setup: DependencyCheck,
+    login_service: DependencyCheck,
+}
+
+#[derive(Debug, Responder)]
+pub struct ReadinessResponse((Status, Json<Readiness>));
+
+impl From<Readiness> for ReadinessResponse {
+    fn from(r: Readiness) -> Self {
+        let status = if r.ready {
+            Status::Ok
+        } else {
+            Status::ServiceUnavailable
+        };
+        Self((status, Json(r)))
+    }
+}
+
+/// Process is alive and able to answer requests
+#[get("/health")]
+pub fn health() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Service dependencies are reachable and requests can be served
+#[get("/ready")]
+pub async fn ready(
+    store: &State<Arc<dyn CacheStore>>,
+    setup: &State<Arc<DatabaseSetup>>,
+    login_service: &State<LoginService>,
+) -> ReadinessResponse {
+    let (database, login_service) = join!(
+        timeout(CHECK_TIMEOUT, store.ping()),
+        timeout(CHECK_TIMEOUT, login_service.ping(CHECK_TIMEOUT)),
+    );
+
+    let database = DependencyCheck::from_result(database);
+    let login_service = DependencyCheck::from_result(login_service);
+    let database_setup = DependencyCheck::from_setup(setup);
+
+    Readiness {
+        ready: database.ready && database_setup.ready && login_service.ready,
+        database,
+        database_setup,
+        login_service,
+    }
+    .into()
+}
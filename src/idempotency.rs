@@ -0,0 +1,181 @@
+use std::{io::Cursor, time::Duration};
+
+use mongodb::bson::DateTime;
+use rocket::{
+    http::{ContentType, Status},
+    request::{FromRequest, Outcome},
+    response::{self, Responder},
+    Request, Response,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    db::{IdempotencyDatabase, IdempotencyRecord, StoredResponse},
+    error::ApiError,
+};
+
+/// Header with key chosen by client for request and all its retries
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Header marking response of earlier request sent again
+pub const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Time key is held for request being processed. Key of request stopped
+/// before its response was saved may be used again after it
+const RESERVATION_LEASE: Duration = Duration::from_secs(60);
+
+/// Time after which record is removed
+fn expires_after(duration: Duration) -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() + duration.as_millis() as i64)
+}
+
+/// Value of `Idempotency-Key` header if client provided it
+#[derive(Debug)]
+pub struct IdempotencyKey(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let key = req
+            .headers()
+            .get_one(IDEMPOTENCY_KEY_HEADER)
+            .map(str::to_string);
+        Outcome::Success(IdempotencyKey(key))
+    }
+}
+
+/// Idempotency state of request being processed
+#[derive(Debug)]
+pub enum Idempotency {
+    /// Request has no key and is processed as usual
+    Disabled,
+    /// Key is used first time. Request must `complete` or `release` it
+    Reserved { id: String, ttl: Duration },
+    /// Response of earlier request with the same key
+    Replay(Replayed),
+}
+
+impl Idempotency {
+    /// Reserves key of user for request. `endpoint` and `request` identify
+    /// request, so reuse of key for different request is rejected
+    pub async fn begin(
+        store: &IdempotencyDatabase,
+        key: IdempotencyKey,
+        user_id: i32,
+        endpoint: &str,
+        request: &impl Serialize,
+        ttl: Duration,
+    ) -> Result<Self, ApiError> {
+        let Some(key) = key.0 else {
+            return Ok(Self::Disabled);
+        };
+        if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+            return Err(ApiError::BadRequest(format!(
+                "{} must be 1 to {} characters long",
+                IDEMPOTENCY_KEY_HEADER, MAX_KEY_LENGTH
+            )));
+        }
+
+        let body = serde_json::to_vec(request).expect("request is always serializable");
+        let mut hasher = Sha256::new();
+        hasher.update(endpoint);
+        hasher.update([0]);
+        hasher.update(body);
+        let fingerprint = hex::encode(hasher.finalize());
+
+        let id = format!("{}:{}", user_id, key);
+        let record = IdempotencyRecord {
+            id: id.clone(),
+            fingerprint: fingerprint.clone(),
+            response: None,
+            expires_at: expires_after(RESERVATION_LEASE),
+        };
+
+        match store.reserve_key(record).await? {
+            None => Ok(Self::Reserved { id, ttl }),
+            Some(existing) if existing.fingerprint != fingerprint => {
+                Err(ApiError::Unprocessable(format!(
+                    "{} was already used for a different request",
+                    IDEMPOTENCY_KEY_HEADER
+                )))
+            }
+            Some(IdempotencyRecord {
+                response: Some(response),
+                ..
+            }) => Ok(Self::Replay(Replayed(response))),
+            Some(_) => Err(ApiError::Conflict(format!(
+                "Request with the same {} is still being processed",
+                IDEMPOTENCY_KEY_HEADER
+            ))),
+        }
+    }
+
+    /// Saves response of request whose changes are stored, for replay until
+    /// TTL ends. Must be called right after the changes, before other work
+    /// which may fail. Failure is only logged, as the changes stay anyway;
+    /// the key is reclaimed after lease then
+    pub async fn complete(
+        self,
+        store: &IdempotencyDatabase,
+        status: Status,
+        body: &impl Serialize,
+    ) {
+        let Self::Reserved { id, ttl } = self else {
+            return;
+        };
+
+        let response = StoredResponse {
+            status: status.code,
+            body: serde_json::to_string(body).expect("response is always serializable"),
+        };
+        if let Err(err) = store.complete_key(&id, response, expires_after(ttl)).await {
+            tracing::error!(error = %err, key = %id, "failed to save idempotent response");
+        }
+    }
+
+    /// Releases key of request failed before changing anything, so the
+    /// request may be retried
+    pub async fn release(self, store: &IdempotencyDatabase) -> Result<(), ApiError> {
+        if let Self::Reserved { id, .. } = self {
+            store.release_key(&id).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Stored response sent again for repeated request
+#[derive(Debug)]
+pub struct Replayed(StoredResponse);
+
+impl<'r> Responder<'r, 'static> for Replayed {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let StoredResponse { status, body } = self.0;
+        Response::build()
+            .status(Status::from_code(status).unwrap_or(Status::Ok))
+            .header(ContentType::JSON)
+            .raw_header(REPLAYED_HEADER, "true")
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+/// Response of handler honoring idempotency keys
+#[derive(Debug)]
+pub enum Idempotent<R> {
+    Fresh(R),
+    Replayed(Replayed),
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for Idempotent<R> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        match self {
+            Idempotent::Fresh(response) => response.respond_to(req),
+            Idempotent::Replayed(response) => response.respond_to(req),
+        }
+    }
+}
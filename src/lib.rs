@@ -0,0 +1,141 @@
+#[macro_use]
+extern crate rocket;
+extern crate mongodb;
+
+use std::time::Duration;
+
+use rocket::{
+    figment::Figment,
+    http::{Header, Status},
+    Build, Request, Rocket,
+};
+
+pub mod cors;
+use cors::RocketCorsEnabler;
+
+pub mod db;
+use db::{RockerDatabaseConnect, RocketSchedulePurge};
+
+pub mod routes;
+use routes::RocketRoutesAdd;
+
+pub mod status;
+use status::Problem;
+
+pub mod error;
+
+pub mod request_id;
+use request_id::RequestId;
+
+pub mod health;
+use health::RocketHealthAdd;
+
+pub mod metrics;
+use metrics::RocketMetricsAdd;
+
+pub mod logging;
+use logging::RocketLoggingAdd;
+
+pub mod auth;
+use auth::AuthError;
+
+pub mod config;
+use config::Config;
+
+pub mod login_service;
+use login_service::RocketAddLoginService;
+
+pub mod rate_limit;
+use rate_limit::{RetryAfter, RocketRateLimitAdd};
+
+pub mod events;
+use events::RocketEventsAdd;
+
+pub mod webhooks;
+use webhooks::RocketWebhooksAdd;
+
+pub mod idempotency;
+
+#[catch(404)]
+pub fn not_found_catcher(req: &Request) -> Problem {
+    let err_msg = format!(
+        "URL: '{}' not found for method {}",
+        req.uri().path().as_str(),
+        req.method().as_str()
+    );
+    Problem::new(req, Status::NotFound, Some(err_msg))
+}
+
+#[catch(500)]
+pub fn unhandled_catcher(req: &Request) -> Problem {
+    tracing::error!(
+        request_id = %RequestId::of(req).0,
+        method = %req.method(),
+        path = %req.uri().path(),
+        "unhandled error"
+    );
+
+    let err_msg = "There are unhandled error. Contact a support with correlation id".to_string();
+    Problem::new(req, Status::InternalServerError, Some(err_msg))
+}
+
+#[derive(Responder)]
+pub struct TooManyRequests(Problem, Header<'static>);
+
+#[catch(429)]
+pub fn too_many_requests_catcher(req: &Request) -> TooManyRequests {
+    let retry_after = RetryAfter::of(req).unwrap_or(Duration::from_secs(1));
+    let err_msg = AuthError::of(req)
+        .map(|err| err.message().to_string())
+        .unwrap_or_else(|| "Too many requests".to_string());
+
+    TooManyRequests(
+        Problem::new(req, Status::TooManyRequests, Some(err_msg)),
+        Header::new(
+            "Retry-After",
+            (retry_after.as_secs_f64().ceil() as u64).to_string(),
+        ),
+    )
+}
+
+#[catch(default)]
+pub fn default_catcher(status: Status, req: &Request) -> Problem {
+    let err_msg = AuthError::of(req).map(|err| err.message().to_string());
+    Problem::new(req, status, err_msg)
+}
+
+/// Builds service from validated configuration. `figment` provides rocket
+/// settings such as address and port
+pub async fn build(figment: Figment, config: Config) -> Rocket<Build> {
+    let api_base = "/api/v1";
+
+    // Rocket trusts `X-Real-IP` by default, which lets clients pick their rate limit key
+    let figment = match config.ip_header() {
+        Some(header) => figment.merge(("ip_header", header.to_string())),
+        None => figment.merge(("ip_header", false)),
+    };
+
+    rocket::custom(figment)
+        .logging_add()
+        .enable_cors(&config)
+        .metrics_add()
+        .connect_database(&config)
+        .await
+        .schedule_purge(&config)
+        .add_login_service(&config)
+        .rate_limit_add(&config)
+        .events_add()
+        .webhooks_add()
+        .manage(config)
+        .register(
+            "/",
+            catchers![
+                not_found_catcher,
+                unhandled_catcher,
+                too_many_requests_catcher,
+                default_catcher
+            ],
+        )
+        .routes_add(api_base)
+        .health_add()
+}
@@ -0,0 +1,84 @@
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Header,
+    Build, Data, Request, Response, Rocket,
+};
+use tracing_subscriber::EnvFilter;
+
+use crate::request_id::{RequestId, RequestStart, REQUEST_ID_HEADER};
+
+/// Used when `RUST_LOG` is not set. Rocket's own per-request messages are
+/// replaced by single line logged by `RequestLogger`
+const DEFAULT_FILTER: &str = "info,rocket=warn,_=warn";
+
+/// Installs JSON logger. Must be called before rocket is built,
+/// otherwise rocket installs its own logger
+pub fn init() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(filter)
+        .with_current_span(false)
+        .init();
+}
+
+/// Id of authenticated user making request, if any
+#[derive(Debug)]
+pub struct RequestUser(pub Option<i32>);
+
+impl RequestUser {
+    /// Remembers user of the request. Only first call during request has effect
+    pub fn set(req: &Request<'_>, user_id: i32) {
+        req.local_cache(|| RequestUser(Some(user_id)));
+    }
+
+    pub fn of(req: &Request<'_>) -> Option<i32> {
+        req.local_cache(|| RequestUser(None)).0
+    }
+}
+
+pub trait RocketLoggingAdd {
+    fn logging_add(self) -> Self;
+}
+
+impl RocketLoggingAdd for Rocket<Build> {
+    fn logging_add(self) -> Self {
+        self.attach(RequestLogger)
+    }
+}
+
+/// Assigns request id to each request and logs results of request
+pub struct RequestLogger;
+
+#[async_trait]
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request logger",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        RequestStart::of(request);
+        RequestId::of(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let request_id = RequestId::of(request);
+        response.set_header(Header::new(REQUEST_ID_HEADER, request_id.0.clone()));
+
+        let latency_ms = RequestStart::of(request).elapsed().as_secs_f64() * 1000.0;
+        tracing::info!(
+            request_id = %request_id.0,
+            method = %request.method(),
+            path = %request.uri().path(),
+            status = response.status().code,
+            latency_ms,
+            user_id = RequestUser::of(request),
+            "request handled"
+        );
+    }
+}
@@ -1,17 +1,23 @@
-use std::env;
+use std::time::Duration;
 
-use reqwest::{RequestBuilder, StatusCode};
+use reqwest::{StatusCode, Url};
 use rocket::{Build, Rocket};
 use serde::Deserialize;
 use serde_json::json;
 
+use crate::{
+    config::Config,
+    metrics::{self, LoginOutcome},
+    request_id::{RequestId, REQUEST_ID_HEADER},
+};
+
 pub trait RocketAddLoginService {
-    fn add_login_service(self) -> Self;
+    fn add_login_service(self, config: &Config) -> Self;
 }
 
 impl RocketAddLoginService for Rocket<Build> {
-    fn add_login_service(self) -> Self {
-        self.manage(LoginService::new())
+    fn add_login_service(self, config: &Config) -> Self {
+        self.manage(LoginService::new(&config.login_service_url()))
     }
 }
 
@@ -19,7 +25,8 @@ impl RocketAddLoginService for Rocket<Build> {
 pub struct LoginService {
     client: reqwest::Client,
 
-    api_path: String,
+    api_url: Url,
+    login_url: Url,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,23 +34,57 @@ pub struct UserId {
     id: i32,
 }
 
+/// Reasons login failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginError {
+    /// Login service does not know the credentials
+    Rejected,
+    /// Login service is not reachable, failed or answered garbage
+    Unavailable,
+}
+
 impl LoginService {
-    pub fn new() -> Self {
+    /// Creates client of login service with base URL `address`,
+    /// which may be given with or without trailing slash
+    pub fn new(address: &Url) -> Self {
         let client = reqwest::Client::new();
 
-        let api_address = env::var("LOGIN_SERVICE_ADDRESS").expect("DATABASE_URL must be set");
+        // Relative paths replace last segment of base without trailing slash
+        let mut base = address.clone();
+        if !base.path().ends_with('/') {
+            let path = format!("{}/", base.path());
+            base.set_path(&path);
+        }
 
-        let api_path = api_address + "api/v1/";
-        Self { client, api_path }
+        let api_url = base.join("api/v1/").expect("API path is valid URL");
+        let login_url = api_url.join("user/login").expect("Login path is valid URL");
+        Self {
+            client,
+            api_url,
+            login_url,
+        }
     }
 
-    fn request_builder_post(&self, path: &str) -> RequestBuilder {
-        self.client.post(format!("{}/{}", self.api_path, path))
+    /// Checks that login service responds. Any HTTP response counts as reachable
+    pub async fn ping(&self, timeout: Duration) -> Result<(), reqwest::Error> {
+        self.client
+            .get(self.api_url.clone())
+            .timeout(timeout)
+            .send()
+            .await
+            .map(|_| ())
     }
 
-    pub async fn login(&self, email: &str, password: &str) -> Option<i32> {
+    pub async fn login(
+        &self,
+        email: &str,
+        password: &str,
+        request_id: &RequestId,
+    ) -> Result<i32, LoginError> {
         let result = self
-            .request_builder_post("user/login")
+            .client
+            .post(self.login_url.clone())
+            .header(REQUEST_ID_HEADER, &request_id.0)
             .json(&json!({
                 "email": email.to_string(),
                 "password": password.to_string(),
@@ -51,27 +92,55 @@ impl LoginService {
             .send()
             .await;
 
-        match result {
-            Ok(response) => match response.status() {
-                StatusCode::OK => {
-                    let j: UserId = response
-                        .json()
-                        .await
-                        .expect("Login service json validation failure");
-                    Some(j.id)
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                tracing::error!(
+                    request_id = %request_id.0,
+                    error = %err,
+                    "failed to access login service"
+                );
+                metrics::record_login_outcome(LoginOutcome::Error);
+                return Err(LoginError::Unavailable);
+            }
+        };
+
+        let status = response.status();
+        if status == StatusCode::OK {
+            return match response.json::<UserId>().await {
+                Ok(user) => {
+                    metrics::record_login_outcome(LoginOutcome::Success);
+                    Ok(user.id)
                 }
-                _ => {
-                    println!(
-                        "Failed to access login service: \nStatus: {}",
-                        response.status()
+                Err(err) => {
+                    tracing::error!(
+                        request_id = %request_id.0,
+                        error = %err,
+                        "login service answered malformed body"
                     );
-                    None
+                    metrics::record_login_outcome(LoginOutcome::Error);
+                    Err(LoginError::Unavailable)
                 }
-            },
-            Err(err) => {
-                println!("Error to access login service: {:?}", err);
-                None
-            }
+            };
+        }
+
+        // Only client errors mean credentials are wrong
+        if status.is_client_error() {
+            tracing::warn!(
+                request_id = %request_id.0,
+                status = status.as_u16(),
+                "login service rejected credentials"
+            );
+            metrics::record_login_outcome(LoginOutcome::Rejected);
+            Err(LoginError::Rejected)
+        } else {
+            tracing::error!(
+                request_id = %request_id.0,
+                status = status.as_u16(),
+                "login service failed"
+            );
+            metrics::record_login_outcome(LoginOutcome::Error);
+            Err(LoginError::Unavailable)
         }
     }
 }
@@ -1,61 +1,90 @@
-#[macro_use]
-extern crate rocket;
-extern crate mongodb;
+use std::process::exit;
 
-use rocket::{serde::json::Json, Request};
+use clap::{Parser, Subcommand};
 
-mod cors;
-use cors::RocketCorsEnabler;
+use msd_cache_service::{
+    config::{Config, StorageBackend},
+    db, logging,
+};
 
-mod db;
-use db::RockerDatabaseConnect;
+/// Geocache service. Serves the API unless a command is given
+#[derive(Parser)]
+#[command(name = "msd-cache-service")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply pending data migrations and exit
+    Migrate {
+        /// Only print what would be changed
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
 
-mod routes;
-use routes::RocketRoutesAdd;
+#[rocket::main]
+async fn main() {
+    let cli = Cli::parse();
 
-mod status;
-use status::ResponseError;
+    // Load .env
+    dotenvy::dotenv().ok();
+    logging::init();
 
-mod auth;
-mod login_service;
-use login_service::RocketAddLoginService;
+    let figment = Config::figment();
+    let config = match Config::load(&figment) {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!("{}", err);
+            exit(1);
+        }
+    };
 
-#[catch(404)]
-pub fn not_found_catcher(req: &Request) -> Json<ResponseError> {
-    let err_msg = format!(
-        "URL: '{}' not found for method {}",
-        req.uri().path().as_str(),
-        req.method().as_str()
-    );
-    Json(ResponseError::new(err_msg))
+    match cli.command {
+        None => {
+            let rocket = msd_cache_service::build(figment, config).await;
+            if let Err(err) = rocket.launch().await {
+                tracing::error!(error = %err, "service stopped with error");
+                exit(1);
+            }
+        }
+        Some(Command::Migrate { dry_run }) => migrate(&config, dry_run).await,
+    }
 }
 
-#[catch(500)]
-pub fn unhandled_catcher(req: &Request) -> Json<ResponseError> {
-    let err_msg = format!(
-        "There are unhandled error in URL '{}' for method {}.
-         Contact a support. With folowing message but make sure to delete keys, password, etc from it:
-         REQUEST:
-         {}",
-        req.uri().path().as_str(),
-        req.method().as_str(),
-        req,
-    );
-    Json(ResponseError::new(err_msg))
-}
+/// Applies pending data migrations and prints what was changed
+async fn migrate(config: &Config, dry_run: bool) {
+    if config.storage_backend != StorageBackend::Mongodb {
+        eprintln!("Migrations are supported for mongodb storage only");
+        exit(1);
+    }
 
-#[launch]
-async fn rocket() -> _ {
-    // Load .env
-    dotenvy::dotenv().ok();
+    let database = db::mongo_database(&config.database()).await;
+    let results = match db::run_migrations(&database, dry_run).await {
+        Ok(results) => results,
+        Err(err) => {
+            tracing::error!(error = %err, "database migration failed");
+            exit(1);
+        }
+    };
 
-    let api_base = "/api/v1";
+    if results.is_empty() {
+        println!("No pending migrations");
+    }
 
-    rocket::build()
-        .enable_cors()
-        .connect_database()
-        .await
-        .add_login_service()
-        .register(api_base, catchers![not_found_catcher, unhandled_catcher])
-        .routes_add(api_base)
+    for result in results {
+        if dry_run {
+            println!(
+                "{}: {} ({} documents to change)",
+                result.name, result.description, result.documents
+            );
+        } else {
+            println!(
+                "{}: {} ({} documents changed)",
+                result.name, result.description, result.documents
+            );
+        }
+    }
 }
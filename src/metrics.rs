@@ -0,0 +1,145 @@
+use std::sync::LazyLock;
+
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, Encoder, HistogramVec,
+    IntCounter, IntCounterVec, TextEncoder,
+};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::ContentType,
+    Build, Data, Request, Response, Rocket,
+};
+
+use crate::request_id::RequestStart;
+
+static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "Count of handled HTTP requests",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time spent handling HTTP requests",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+static DATABASE_ERRORS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "database_errors_total",
+        "Count of MongoDB errors returned to clients"
+    )
+    .unwrap()
+});
+
+static LOGIN_SERVICE_CALLS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "login_service_calls_total",
+        "Count of login service calls by outcome",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
+/// Result of a call to login service
+#[derive(Debug, Clone, Copy)]
+pub enum LoginOutcome {
+    /// Credentials accepted
+    Success,
+    /// Login service answered with non-success status
+    Rejected,
+    /// Login service is not reachable or answered garbage
+    Error,
+}
+
+impl LoginOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            LoginOutcome::Success => "success",
+            LoginOutcome::Rejected => "rejected",
+            LoginOutcome::Error => "error",
+        }
+    }
+}
+
+pub fn record_database_error() {
+    DATABASE_ERRORS.inc();
+}
+
+pub fn record_login_outcome(outcome: LoginOutcome) {
+    LOGIN_SERVICE_CALLS
+        .with_label_values(&[outcome.as_str()])
+        .inc();
+}
+
+pub trait RocketMetricsAdd {
+    fn metrics_add(self) -> Self;
+}
+
+impl RocketMetricsAdd for Rocket<Build> {
+    fn metrics_add(self) -> Self {
+        // Register all metrics up front so they are exported before first use
+        LazyLock::force(&HTTP_REQUESTS);
+        LazyLock::force(&HTTP_REQUEST_DURATION);
+        LazyLock::force(&DATABASE_ERRORS);
+        LazyLock::force(&LOGIN_SERVICE_CALLS);
+
+        self.attach(Metrics).mount("/", routes![metrics])
+    }
+}
+
+pub struct Metrics;
+
+#[async_trait]
+impl Fairing for Metrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Prometheus metrics collector",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        RequestStart::of(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let elapsed = RequestStart::of(request).elapsed().as_secs_f64();
+
+        // Route templates keep labels cardinality bounded unlike raw paths
+        let route = request
+            .route()
+            .map(|r| r.uri.as_str())
+            .unwrap_or("unmatched");
+        let status = response.status().code.to_string();
+        let labels = [request.method().as_str(), route, status.as_str()];
+
+        HTTP_REQUESTS.with_label_values(&labels).inc();
+        HTTP_REQUEST_DURATION
+            .with_label_values(&labels)
+            .observe(elapsed);
+    }
+}
+
+/// All collected metrics in Prometheus text format
+#[get("/metrics")]
+pub fn metrics() -> (ContentType, String) {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder
+        .encode(&prometheus::gather(), &mut buffer)
+        .expect("metrics are always encodable");
+
+    let content_type =
+        ContentType::parse_flexible(encoder.format_type()).unwrap_or(ContentType::Plain);
+    (
+        content_type,
+        String::from_utf8(buffer).expect("text format is valid UTF-8"),
+    )
+}
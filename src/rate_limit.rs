@@ -0,0 +1,281 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::BuildHasher,
+    net::{IpAddr, Ipv4Addr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Build, Request, Rocket, State,
+};
+
+use crate::{auth::AuthInfo, config::Config, logging::RequestUser};
+
+/// Buckets are dropped once map grows over this size if they are full again
+const MAX_BUCKETS: usize = 10_000;
+
+/// Remembered users of credentials are forgotten once there are more of them
+const MAX_CREDENTIALS: usize = 10_000;
+
+/// Kind of requests sharing one budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Budget {
+    Read,
+    Write,
+    /// Failed authentication attempts
+    AuthFailure,
+}
+
+/// Who spends the budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientKey {
+    User(i32),
+    Ip(IpAddr),
+}
+
+impl ClientKey {
+    /// Authenticated user if known, otherwise client IP
+    pub fn of(req: &Request<'_>) -> Self {
+        match RequestUser::of(req) {
+            Some(user_id) => ClientKey::User(user_id),
+            None => Self::ip_of(req),
+        }
+    }
+
+    pub fn ip_of(req: &Request<'_>) -> Self {
+        ClientKey::Ip(req.client_ip().unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)))
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket refilled with `per_minute` tokens each minute and holding at most `per_minute`
+#[derive(Debug, Clone, Copy)]
+struct Limit {
+    per_minute: u32,
+}
+
+impl Limit {
+    fn capacity(&self) -> f64 {
+        self.per_minute as f64
+    }
+
+    fn refill_per_second(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_second()).min(self.capacity());
+        bucket.updated = now;
+    }
+
+    /// Time until bucket has one token
+    fn retry_after(&self, bucket: &Bucket) -> Duration {
+        let missing = 1.0 - bucket.tokens;
+        Duration::from_secs_f64((missing / self.refill_per_second()).max(0.0))
+            .max(Duration::from_secs(1))
+    }
+}
+
+/// Token bucket rate limiter with separate budgets for reads, writes and auth failures
+#[derive(Debug)]
+pub struct RateLimiter {
+    read: Limit,
+    write: Limit,
+    auth_failure: Limit,
+    buckets: Mutex<HashMap<(Budget, ClientKey), Bucket>>,
+    /// Users authenticated by `Authorization` header, keyed by its hash.
+    /// Only used to reject writes over budget without asking login
+    /// service, never to authenticate
+    credential_users: Mutex<HashMap<u64, i32>>,
+    credential_hasher: RandomState,
+}
+
+impl RateLimiter {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            read: Limit {
+                per_minute: config.rate_limit_reads_per_minute,
+            },
+            write: Limit {
+                per_minute: config.rate_limit_writes_per_minute,
+            },
+            auth_failure: Limit {
+                per_minute: config.rate_limit_auth_failures_per_minute,
+            },
+            buckets: Mutex::new(HashMap::new()),
+            credential_users: Mutex::new(HashMap::new()),
+            credential_hasher: RandomState::new(),
+        }
+    }
+
+    fn limit(&self, budget: Budget) -> Limit {
+        match budget {
+            Budget::Read => self.read,
+            Budget::Write => self.write,
+            Budget::AuthFailure => self.auth_failure,
+        }
+    }
+
+    /// Takes token from the budget. Returns time to wait if budget is exhausted
+    pub fn take(&self, budget: Budget, key: ClientKey) -> Result<(), Duration> {
+        self.with_bucket(budget, key, |limit, bucket| {
+            if bucket.tokens < 1.0 {
+                return Err(limit.retry_after(bucket));
+            }
+            bucket.tokens -= 1.0;
+            Ok(())
+        })
+    }
+
+    /// Checks that budget has tokens without spending them
+    pub fn check(&self, budget: Budget, key: ClientKey) -> Result<(), Duration> {
+        self.with_bucket(budget, key, |limit, bucket| {
+            if bucket.tokens < 1.0 {
+                return Err(limit.retry_after(bucket));
+            }
+            Ok(())
+        })
+    }
+
+    /// User authenticated before with credentials of request
+    fn user_of_credentials(&self, req: &Request<'_>) -> Option<i32> {
+        let key = self.credentials_key(req)?;
+        self.credential_users.lock().unwrap().get(&key).copied()
+    }
+
+    fn remember_credentials(&self, req: &Request<'_>, user_id: i32) {
+        let Some(key) = self.credentials_key(req) else {
+            return;
+        };
+        let mut users = self.credential_users.lock().unwrap();
+        if users.len() >= MAX_CREDENTIALS {
+            users.clear();
+        }
+        users.insert(key, user_id);
+    }
+
+    fn credentials_key(&self, req: &Request<'_>) -> Option<u64> {
+        let header = req.headers().get_one("Authorization")?;
+        Some(self.credential_hasher.hash_one(header))
+    }
+
+    fn with_bucket<R>(
+        &self,
+        budget: Budget,
+        key: ClientKey,
+        f: impl FnOnce(Limit, &mut Bucket) -> R,
+    ) -> R {
+        let limit = self.limit(budget);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() > MAX_BUCKETS {
+            buckets.retain(|(budget, _), bucket| {
+                let limit = self.limit(*budget);
+                limit.refill(bucket, now);
+                bucket.tokens < limit.capacity()
+            });
+        }
+
+        let bucket = buckets.entry((budget, key)).or_insert(Bucket {
+            tokens: limit.capacity(),
+            updated: now,
+        });
+        limit.refill(bucket, now);
+        f(limit, bucket)
+    }
+}
+
+/// Time client should wait before next request, kept for error catchers
+#[derive(Debug)]
+pub struct RetryAfter(pub Option<Duration>);
+
+impl RetryAfter {
+    pub fn set(req: &Request<'_>, after: Duration) {
+        req.local_cache(|| RetryAfter(Some(after)));
+    }
+
+    pub fn of(req: &Request<'_>) -> Option<Duration> {
+        req.local_cache(|| RetryAfter(None)).0
+    }
+}
+
+pub trait RocketRateLimitAdd {
+    fn rate_limit_add(self, config: &Config) -> Self;
+}
+
+impl RocketRateLimitAdd for Rocket<Build> {
+    fn rate_limit_add(self, config: &Config) -> Self {
+        self.manage(RateLimiter::new(config))
+    }
+}
+
+async fn take(req: &Request<'_>, budget: Budget) -> Outcome<(), ()> {
+    let limiter: &State<RateLimiter> = req
+        .guard()
+        .await
+        .expect("Rate limiter must be added to rocket");
+
+    match limiter.take(budget, ClientKey::of(req)) {
+        Ok(_) => Outcome::Success(()),
+        Err(after) => {
+            RetryAfter::set(req, after);
+            Outcome::Error((Status::TooManyRequests, ()))
+        }
+    }
+}
+
+/// Spends read budget of user or IP
+pub struct ReadLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ReadLimit {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        take(req, Budget::Read).await.map(|_| ReadLimit)
+    }
+}
+
+/// Spends write budget of authenticated user. Routes take it before
+/// `AuthInfo`, so writes over budget are rejected before authentication
+pub struct WriteLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for WriteLimit {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiter: &State<RateLimiter> = req
+            .guard()
+            .await
+            .expect("Rate limiter must be added to rocket");
+
+        // Client writing over budget with credentials seen before is
+        // rejected without asking login service again
+        if let Some(user_id) = limiter.user_of_credentials(req) {
+            if let Err(after) = limiter.check(Budget::Write, ClientKey::User(user_id)) {
+                RetryAfter::set(req, after);
+                return Outcome::Error((Status::TooManyRequests, ()));
+            }
+        }
+
+        // Authenticate first so budget is spent by user, not by shared IP
+        match req.guard::<AuthInfo>().await {
+            Outcome::Success(auth) => limiter.remember_credentials(req, auth.user_id),
+            Outcome::Error((status, _)) => return Outcome::Error((status, ())),
+            Outcome::Forward(_) => {}
+        }
+
+        take(req, Budget::Write).await.map(|_| WriteLimit)
+    }
+}
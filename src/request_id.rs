@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+use mongodb::bson::oid::ObjectId;
+use rocket::{
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+/// Header used to pass request id between services
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest request id accepted from client
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// Client provided id is echoed, logged and forwarded to other services,
+/// so only short ids of safe characters are accepted
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Identifier of current request.
+/// Taken from `X-Request-Id` header if client provided valid one, otherwise generated
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// Returns id of the request. Same value returned for all calls during one request
+    pub fn of<'r>(req: &'r Request<'_>) -> &'r RequestId {
+        req.local_cache(|| {
+            let id = req
+                .headers()
+                .get_one(REQUEST_ID_HEADER)
+                .filter(|id| is_valid(id))
+                .map(str::to_string)
+                .unwrap_or_else(|| ObjectId::new().to_hex());
+            RequestId(id)
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId::of(req).clone())
+    }
+}
+
+/// Time when processing of current request started
+#[derive(Debug)]
+pub struct RequestStart(Instant);
+
+impl RequestStart {
+    /// Returns start time of the request. First call during request defines it
+    pub fn of<'r>(req: &'r Request<'_>) -> &'r RequestStart {
+        req.local_cache(|| RequestStart(Instant::now()))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
@@ -0,0 +1,80 @@
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+
+use super::{find_caches, CacheKey};
+use crate::{
+    db::{Cache, CacheDatabase},
+    error::ApiError,
+    rate_limit::ReadLimit,
+};
+
+/// Most caches requested at once
+const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct CacheBatchRequest {
+    /// Cache ids or short codes
+    ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheBatch {
+    /// Found caches in order of request
+    caches: Vec<Cache>,
+    /// Requested values which are neither ids nor short codes
+    invalid: Vec<String>,
+    /// Requested caches which do not exist or are deleted
+    not_found: Vec<String>,
+}
+
+#[derive(Debug, Responder)]
+pub struct CacheBatchResponse(Json<CacheBatch>);
+
+impl From<CacheBatch> for CacheBatchResponse {
+    fn from(v: CacheBatch) -> Self {
+        Self(Json(v))
+    }
+}
+
+#[post("/batch", format = "json", data = "<request>")]
+pub async fn view_cache_batch(
+    request: Json<CacheBatchRequest>,
+    cache_db: CacheDatabase,
+    _limit: ReadLimit,
+) -> Result<CacheBatchResponse, ApiError> {
+    if request.ids.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} caches can be requested at once",
+            MAX_BATCH_SIZE
+        )));
+    }
+
+    let mut keys = Vec::new();
+    let mut invalid = Vec::new();
+    for value in &request.ids {
+        match CacheKey::parse(value) {
+            Some(key) => keys.push((value, key)),
+            None => invalid.push(value.clone()),
+        }
+    }
+
+    let found = find_caches(&cache_db, keys.iter().map(|(_, key)| key)).await?;
+
+    // Cache requested twice, e.g. by id and by code, is returned once
+    let mut caches: Vec<Cache> = Vec::new();
+    let mut not_found = Vec::new();
+    for (value, key) in keys {
+        match found.iter().find(|cache| key.matches(cache)) {
+            Some(cache) if !caches.iter().any(|c| c.id == cache.id) => caches.push(cache.clone()),
+            Some(_) => {}
+            None => not_found.push(value.clone()),
+        }
+    }
+
+    Ok(CacheBatch {
+        caches,
+        invalid,
+        not_found,
+    }
+    .into())
+}
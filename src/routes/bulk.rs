@@ -0,0 +1,291 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rocket::{http::Status, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+
+use super::{find_caches, CacheKey};
+use crate::{
+    auth::AuthInfo,
+    config::Config,
+    db::{
+        AuditAction, AuditDatabase, AuditEntry, Cache, CacheDatabase, CacheWrite, CacheWritten,
+        IdempotencyDatabase,
+    },
+    error::ApiError,
+    events::{CacheEventKind, EventBus},
+    idempotency::{Idempotency, IdempotencyKey, Idempotent},
+    rate_limit::WriteLimit,
+    request_id::RequestId,
+};
+
+/// Most operations accepted at once
+const MAX_OPERATIONS: usize = 100;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkOperation {
+    Create {
+        cache: Cache,
+    },
+    /// Cache is given by id or short code
+    Update {
+        id: String,
+        cache: Cache,
+    },
+    Delete {
+        id: String,
+    },
+}
+
+impl BulkOperation {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Create { .. } => "create",
+            Self::Update { .. } => "update",
+            Self::Delete { .. } => "delete",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkRequest {
+    operations: Vec<BulkOperation>,
+}
+
+/// Result of operation in the same position of request
+#[derive(Debug, Serialize)]
+pub struct BulkResult {
+    op: &'static str,
+    /// HTTP status the operation would get as a separate request
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<ObjectId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl BulkResult {
+    fn ok(op: &'static str, status: Status, cache: &Cache) -> Self {
+        Self {
+            op,
+            status: status.code,
+            id: cache.id,
+            code: cache.code.clone(),
+            error: None,
+        }
+    }
+
+    fn error(op: &'static str, status: Status, error: &str) -> Self {
+        Self {
+            op,
+            status: status.code,
+            id: None,
+            code: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkResults {
+    results: Vec<BulkResult>,
+}
+
+#[derive(Debug, Responder)]
+pub struct BulkResponse(Json<BulkResults>);
+
+impl From<BulkResults> for BulkResponse {
+    fn from(v: BulkResults) -> Self {
+        Self(Json(v))
+    }
+}
+
+/// Checks operation against current caches and turns it into storage write
+fn plan(
+    operation: BulkOperation,
+    key: Option<&CacheKey>,
+    found: &[Cache],
+    auth: &AuthInfo,
+    now: DateTime,
+) -> Result<CacheWrite, (Status, String)> {
+    let target = || {
+        let Some(key) = key else {
+            return Err((Status::BadRequest, "Wrong ObjectID format".to_string()));
+        };
+        let Some(current) = found.iter().find(|cache| key.matches(cache)) else {
+            return Err((Status::NotFound, "Cache not found".to_string()));
+        };
+        if !auth.can_manage(current.owner_id) {
+            return Err((
+                Status::Forbidden,
+                "Only owner or moderator can change cache".to_string(),
+            ));
+        }
+        Ok(current.id.expect("stored cache has id"))
+    };
+
+    match operation {
+        BulkOperation::Create { mut cache } => {
+            cache.validate().map_err(|err| (Status::BadRequest, err))?;
+            cache.id = None;
+            cache.code = None;
+            cache.owner_id = Some(auth.user_id);
+            cache.deleted_at = None;
+            cache.created_at = Some(now);
+            cache.updated_at = Some(now);
+            Ok(CacheWrite::Insert(cache))
+        }
+        BulkOperation::Update { mut cache, .. } => {
+            let id = target()?;
+            cache.validate().map_err(|err| (Status::BadRequest, err))?;
+            cache.id = Some(id);
+            Ok(CacheWrite::Update(cache))
+        }
+        BulkOperation::Delete { .. } => Ok(CacheWrite::Delete(target()?)),
+    }
+}
+
+/// Repeated request with the same `Idempotency-Key` gets the first response
+#[post("/bulk", format = "json", data = "<request>")]
+pub async fn bulk_caches(
+    request: Json<BulkRequest>,
+    cache_db: CacheDatabase,
+    audit_db: AuditDatabase,
+    idempotency_db: IdempotencyDatabase,
+    _limit: WriteLimit,
+    auth: AuthInfo,
+    request_id: RequestId,
+    key: IdempotencyKey,
+    events: &State<EventBus>,
+    config: &State<Config>,
+) -> Result<Idempotent<BulkResponse>, ApiError> {
+    let idempotency = Idempotency::begin(
+        &idempotency_db,
+        key,
+        auth.user_id,
+        "bulk_caches",
+        &request.0,
+        config.idempotency_ttl(),
+    )
+    .await?;
+    if let Idempotency::Replay(replayed) = idempotency {
+        return Ok(Idempotent::Replayed(replayed));
+    }
+
+    let (response, written) = match run_bulk(request.0.operations, &cache_db, &auth).await {
+        Ok(applied) => applied,
+        Err(err) => {
+            idempotency.release(&idempotency_db).await?;
+            return Err(err);
+        }
+    };
+    idempotency
+        .complete(&idempotency_db, Status::Ok, &response.0 .0)
+        .await;
+    record_written(written, &audit_db, &auth, &request_id, events).await;
+    Ok(Idempotent::Fresh(response))
+}
+
+/// Checks and applies operations. Returns response and stored writes
+async fn run_bulk(
+    operations: Vec<BulkOperation>,
+    cache_db: &CacheDatabase,
+    auth: &AuthInfo,
+) -> Result<(BulkResponse, Vec<CacheWritten>), ApiError> {
+    if operations.len() > MAX_OPERATIONS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} operations can be applied at once",
+            MAX_OPERATIONS
+        )));
+    }
+
+    let keys: Vec<_> = operations
+        .iter()
+        .map(|operation| match operation {
+            BulkOperation::Create { .. } => None,
+            BulkOperation::Update { id, .. } | BulkOperation::Delete { id } => CacheKey::parse(id),
+        })
+        .collect();
+    let found = find_caches(cache_db, keys.iter().flatten()).await?;
+
+    // Operations failed checks get results now, others keep their name until written
+    let now = DateTime::now();
+    let mut results: Vec<Result<BulkResult, &'static str>> = Vec::new();
+    let mut writes = Vec::new();
+    for (operation, key) in operations.into_iter().zip(&keys) {
+        let op = operation.name();
+        match plan(operation, key.as_ref(), &found, auth, now) {
+            Ok(write) => {
+                writes.push(write);
+                results.push(Err(op));
+            }
+            Err((status, error)) => results.push(Ok(BulkResult::error(op, status, &error))),
+        }
+    }
+
+    let written = if writes.is_empty() {
+        Vec::new()
+    } else {
+        cache_db.bulk_write(writes).await?
+    };
+
+    let mut writes_left = written.iter();
+    for result in results.iter_mut() {
+        let Err(op) = *result else {
+            continue;
+        };
+        let written = writes_left
+            .next()
+            .expect("storage returns result of each write");
+        *result = Ok(match written {
+            CacheWritten::Inserted(cache) => BulkResult::ok(op, Status::Created, cache),
+            CacheWritten::Updated { after, .. } => BulkResult::ok(op, Status::Ok, after),
+            CacheWritten::Deleted(before) => BulkResult::ok(op, Status::Ok, before),
+            // Cache was changed by another request after checks
+            CacheWritten::NotFound => BulkResult::error(op, Status::NotFound, "Cache not found"),
+            CacheWritten::Failed => {
+                BulkResult::error(op, Status::InternalServerError, "Failed to apply operation")
+            }
+        });
+    }
+
+    let response = BulkResults {
+        results: results.into_iter().flatten().collect(),
+    };
+    Ok((response.into(), written))
+}
+
+/// Audits and publishes stored writes. Failure here must not fail the
+/// request, as the writes stay anyway
+async fn record_written(
+    written: Vec<CacheWritten>,
+    audit_db: &AuditDatabase,
+    auth: &AuthInfo,
+    request_id: &RequestId,
+    events: &EventBus,
+) {
+    for written in &written {
+        let (action, id, before, after) = match written {
+            CacheWritten::Inserted(cache) => {
+                events.publish(CacheEventKind::Created, cache, None);
+                (AuditAction::Create, cache.id, None, Some(cache))
+            }
+            CacheWritten::Updated { before, after } => {
+                events.publish(CacheEventKind::Updated, after, Some(before));
+                (AuditAction::Edit, after.id, Some(before), Some(after))
+            }
+            CacheWritten::Deleted(before) => {
+                events.publish(CacheEventKind::Deleted, before, None);
+                (AuditAction::Delete, before.id, Some(before), None)
+            }
+            CacheWritten::NotFound | CacheWritten::Failed => continue,
+        };
+
+        let id = id.expect("written cache has id");
+        let entry = AuditEntry::new(auth.user_id, action, id, before, after, request_id);
+        if let Err(err) = audit_db.record(entry).await {
+            tracing::error!(error = %err, cache_id = %id, "failed to record bulk cache write");
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rocket::serde::json::Json;
+use serde::Serialize;
+
+use crate::{
+    db::{parse_bbox, CacheDatabase, TOMBSTONE_RETENTION},
+    error::ApiError,
+    rate_limit::ReadLimit,
+};
+
+/// Changes saved shortly before request may become visible after it,
+/// so next sync starts a bit earlier and may repeat some changes
+const SYNC_LAG: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+pub struct CacheChanges {
+    /// Caches created or changed since token
+    upserted: Vec<ObjectId>,
+    /// Caches deleted since token
+    deleted: Vec<ObjectId>,
+    /// Token for the next sync
+    token: String,
+}
+
+#[derive(Debug, Responder)]
+pub struct CacheChangesResponse(Json<CacheChanges>);
+
+impl From<CacheChanges> for CacheChangesResponse {
+    fn from(v: CacheChanges) -> Self {
+        Self(Json(v))
+    }
+}
+
+/// Sync token is opaque for clients, it holds time of previous sync in milliseconds
+fn parse_token(token: &str) -> Result<DateTime, ApiError> {
+    token
+        .parse()
+        .map(DateTime::from_millis)
+        .map_err(|_| ApiError::BadRequest("Invalid sync token".to_string()))
+}
+
+/// Returns caches in area changed since previous sync. Without `since`
+/// returns all caches in area to make initial copy
+#[get("/changes?<since>&<bbox>")]
+pub async fn view_changes(
+    since: Option<&str>,
+    bbox: Option<&str>,
+    cache_db: CacheDatabase,
+    _limit: ReadLimit,
+) -> Result<CacheChangesResponse, ApiError> {
+    let since = since.map(parse_token).transpose()?;
+    let bounds = bbox
+        .map(parse_bbox)
+        .transpose()
+        .map_err(|err| ApiError::BadRequest(format!("Invalid bbox: {}", err)))?;
+
+    let now = DateTime::now().timestamp_millis();
+    if let Some(since) = since {
+        if since.timestamp_millis() < now - TOMBSTONE_RETENTION.as_millis() as i64 {
+            return Err(ApiError::Gone(
+                "Sync token expired, download caches again".to_string(),
+            ));
+        }
+    }
+
+    let changes = cache_db.get_changes(since, bounds).await?;
+    let (deleted, upserted): (Vec<_>, Vec<_>) = changes.into_iter().partition(|c| c.deleted);
+
+    let next =
+        (now - SYNC_LAG.as_millis() as i64).max(since.map(|s| s.timestamp_millis()).unwrap_or(0));
+
+    Ok(CacheChanges {
+        upserted: upserted.into_iter().map(|c| c.id).collect(),
+        deleted: deleted.into_iter().map(|c| c.id).collect(),
+        token: next.to_string(),
+    }
+    .into())
+}
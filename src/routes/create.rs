@@ -1,51 +1,105 @@
 use crate::{
     auth::AuthInfo,
-    db::{Cache, CacheDatabase, DatabaseErrorResponse},
+    config::Config,
+    db::{AuditAction, AuditDatabase, AuditEntry, Cache, CacheDatabase, IdempotencyDatabase},
+    error::ApiError,
+    events::{CacheEventKind, EventBus},
+    idempotency::{Idempotency, IdempotencyKey, Idempotent},
+    rate_limit::WriteLimit,
+    request_id::RequestId,
+};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rocket::{
+    http::Status,
+    serde::json::{Json, Value},
+    State,
 };
-use mongodb::bson::oid::ObjectId;
-use rocket::serde::json::{Json, Value};
 use serde_json::json;
 
 #[derive(Responder)]
 #[response(status = 201)]
 pub struct CacheAdded(Json<Value>);
 impl CacheAdded {
-    pub fn new(id: ObjectId) -> Self {
+    pub fn new(id: ObjectId, code: &str) -> Self {
         Self(Json(json! ({
             "id": id,
+            "code": code,
         })))
     }
 }
 
-pub enum CacheError {
-    DatabaseError(mongodb::error::Error),
-}
-
-#[derive(Debug, Responder)]
-pub enum CacheErrorResponse {
-    DBError(DatabaseErrorResponse),
-}
-
-impl From<CacheError> for CacheErrorResponse {
-    fn from(err: CacheError) -> Self {
-        match err {
-            CacheError::DatabaseError(db_err) => Self::DBError(DatabaseErrorResponse::new(db_err)),
-        }
-    }
-}
-
+/// Repeated request with the same `Idempotency-Key` gets the first response
 #[post("/", format = "json", data = "<cache>")]
 pub async fn create_cache(
     cache: Json<Cache>,
     cache_db: CacheDatabase,
+    audit_db: AuditDatabase,
+    idempotency_db: IdempotencyDatabase,
+    _limit: WriteLimit,
     auth: AuthInfo,
-) -> Result<CacheAdded, CacheErrorResponse> {
+    request_id: RequestId,
+    key: IdempotencyKey,
+    events: &State<EventBus>,
+    config: &State<Config>,
+) -> Result<Idempotent<CacheAdded>, ApiError> {
+    let idempotency = Idempotency::begin(
+        &idempotency_db,
+        key,
+        auth.user_id,
+        "create_cache",
+        &cache.0,
+        config.idempotency_ttl(),
+    )
+    .await?;
+    if let Idempotency::Replay(replayed) = idempotency {
+        return Ok(Idempotent::Replayed(replayed));
+    }
+
+    let cache_added = match add_cache(cache.0, &cache_db, &auth).await {
+        Ok(cache_added) => cache_added,
+        Err(err) => {
+            idempotency.release(&idempotency_db).await?;
+            return Err(err);
+        }
+    };
+    let id = cache_added.id.expect("inserted cache has id");
+    let added = CacheAdded::new(id, cache_added.code.as_deref().unwrap_or_default());
+    idempotency
+        .complete(&idempotency_db, Status::Created, &added.0 .0)
+        .await;
+
+    // Cache is stored, so failure here must not fail the request
+    let entry = AuditEntry::new(
+        auth.user_id,
+        AuditAction::Create,
+        id,
+        None,
+        Some(&cache_added),
+        &request_id,
+    );
+    if let Err(err) = audit_db.record(entry).await {
+        tracing::error!(error = %err, cache_id = %id, "failed to record cache creation");
+    }
+    events.publish(CacheEventKind::Created, &cache_added, None);
+
+    Ok(Idempotent::Fresh(added))
+}
+
+async fn add_cache(
+    cache: Cache,
+    cache_db: &CacheDatabase,
+    auth: &AuthInfo,
+) -> Result<Cache, ApiError> {
+    cache.validate().map_err(ApiError::BadRequest)?;
+
     // Set user id as owner
-    let mut cache_to_add = cache.0;
+    let mut cache_to_add = cache;
+    cache_to_add.code = None;
     cache_to_add.owner_id = Some(auth.user_id);
+    cache_to_add.deleted_at = None;
+    let now = DateTime::now();
+    cache_to_add.created_at = Some(now);
+    cache_to_add.updated_at = Some(now);
 
-    match cache_db.insert_cache(cache_to_add).await {
-        Ok(id) => Ok(CacheAdded::new(id)),
-        Err(e) => Err(CacheError::DatabaseError(e).into()),
-    }
+    Ok(cache_db.insert_cache(cache_to_add).await?)
 }
@@ -1,37 +1,23 @@
+use super::cache_id;
 use crate::{
     auth::AuthInfo,
-    db::{CacheDatabase, DatabaseErrorResponse},
-    status::ResponseError,
+    db::{AuditAction, AuditDatabase, AuditEntry, CacheDatabase},
+    error::ApiError,
+    events::{CacheEventKind, EventBus},
+    rate_limit::WriteLimit,
+    request_id::RequestId,
+};
+use rocket::{
+    serde::json::{Json, Value},
+    State,
 };
-use mongodb::bson::oid::ObjectId;
-use rocket::serde::json::{Json, Value};
 use serde_json::json;
 
-pub enum DeleteResult {
-    Ok,
-    DBError(mongodb::error::Error),
-    WrongObjectID,
-}
-
 #[derive(Debug, Responder)]
-pub enum DeleteResultResponse {
-    #[response(status = 200)]
-    Ok(Json<Value>),
-    DBError(DatabaseErrorResponse),
-
-    #[response(status = 400)]
-    WrongObjectID(Json<ResponseError>),
-}
-
-impl From<DeleteResult> for DeleteResultResponse {
-    fn from(res: DeleteResult) -> Self {
-        match res {
-            DeleteResult::Ok => Self::Ok(Json(json!({}))),
-            DeleteResult::DBError(err) => Self::DBError(DatabaseErrorResponse::new(err)),
-            DeleteResult::WrongObjectID => Self::WrongObjectID(Json(ResponseError::new(
-                "Wrong ObjectID format".to_string(),
-            ))),
-        }
+pub struct CacheDeleted(Json<Value>);
+impl CacheDeleted {
+    pub fn new() -> Self {
+        Self(Json(json!({})))
     }
 }
 
@@ -39,14 +25,42 @@ impl From<DeleteResult> for DeleteResultResponse {
 pub async fn delete_cache(
     id: String,
     cache_db: CacheDatabase,
-    _auth: AuthInfo,
-) -> DeleteResultResponse {
-    let Ok(oid) = ObjectId::parse_str(&id) else {
-        return DeleteResult::WrongObjectID.into();
+    audit_db: AuditDatabase,
+    _limit: WriteLimit,
+    auth: AuthInfo,
+    request_id: RequestId,
+    events: &State<EventBus>,
+) -> Result<CacheDeleted, ApiError> {
+    let oid = cache_id(&cache_db, &id).await?;
+
+    // Nothing deleted if cache does not exist
+    let Some(current) = cache_db.get_cache_by_id(oid).await? else {
+        return Ok(CacheDeleted::new());
     };
+    if !auth.can_manage(current.owner_id) {
+        return Err(ApiError::Forbidden(
+            "Only owner or moderator can delete cache".to_string(),
+        ));
+    }
 
-    match cache_db.delete_cache_by_id(oid).await {
-        Ok(_) => DeleteResult::Ok.into(),
-        Err(err) => DeleteResult::DBError(err).into(),
+    // Cache may be deleted since it was checked
+    let Some(before) = cache_db.delete_cache_by_id(oid).await? else {
+        return Ok(CacheDeleted::new());
+    };
+
+    // Cache is deleted, so failure here must not fail the request
+    let entry = AuditEntry::new(
+        auth.user_id,
+        AuditAction::Delete,
+        oid,
+        Some(&before),
+        None,
+        &request_id,
+    );
+    if let Err(err) = audit_db.record(entry).await {
+        tracing::error!(error = %err, cache_id = %oid, "failed to record cache deletion");
     }
+    events.publish(CacheEventKind::Deleted, &before, None);
+
+    Ok(CacheDeleted::new())
 }
@@ -1,11 +1,14 @@
-use mongodb::bson::oid::ObjectId;
-use rocket::serde::json::Json;
+use rocket::{serde::json::Json, State};
 use serde_json::{json, Value};
 
+use super::cache_id;
 use crate::{
     auth::AuthInfo,
-    db::{Cache, CacheDatabase, DatabaseErrorResponse},
-    status::ResponseError,
+    db::{AuditAction, AuditDatabase, AuditEntry, Cache, CacheDatabase},
+    error::ApiError,
+    events::{CacheEventKind, EventBus},
+    rate_limit::WriteLimit,
+    request_id::RequestId,
 };
 
 #[derive(Debug, Responder)]
@@ -16,46 +19,62 @@ impl CacheEditResponse {
     }
 }
 
-pub enum CacheEditError {
-    WrongObjectID,
-    DBError(mongodb::error::Error),
-}
-
-#[derive(Debug, Responder)]
-pub enum CacheEditErrorResponse {
-    #[response(status = 400)]
-    WrongObjectID(Json<ResponseError>),
-
-    DBError(DatabaseErrorResponse),
-}
-
-impl From<CacheEditError> for CacheEditErrorResponse {
-    fn from(err: CacheEditError) -> Self {
-        match err {
-            CacheEditError::WrongObjectID => Self::WrongObjectID(Json(ResponseError::new(
-                "Wrong ObjectID format".to_string(),
-            ))),
-            CacheEditError::DBError(err) => Self::DBError(DatabaseErrorResponse::new(err)),
-        }
-    }
-}
-
 #[put("/<id>", format = "json", data = "<cache>")]
 pub async fn edit_cache(
     id: String,
     cache: Json<Cache>,
     cache_db: CacheDatabase,
-    _auth: AuthInfo,
-) -> Result<CacheEditResponse, CacheEditErrorResponse> {
-    let Ok(oid) = ObjectId::parse_str(&id) else {
-        return Err(CacheEditError::WrongObjectID.into());
+    audit_db: AuditDatabase,
+    _limit: WriteLimit,
+    auth: AuthInfo,
+    request_id: RequestId,
+    events: &State<EventBus>,
+) -> Result<CacheEditResponse, ApiError> {
+    let oid = cache_id(&cache_db, &id).await?;
+
+    cache.validate().map_err(ApiError::BadRequest)?;
+
+    // Nothing changed if cache does not exist
+    let Some(current) = cache_db.get_cache_by_id(oid).await? else {
+        return Ok(CacheEditResponse::new());
     };
+    if !auth.can_manage(current.owner_id) {
+        return Err(ApiError::Forbidden(
+            "Only owner or moderator can change cache".to_string(),
+        ));
+    }
 
     let mut cache_new = cache.0;
     cache_new.id = Some(oid);
 
-    match cache_db.update_cache(cache_new).await {
-        Ok(_) => Ok(CacheEditResponse::new()),
-        Err(err) => Err(CacheEditError::DBError(err).into()),
+    // Cache may be deleted since it was checked
+    let Some(before) = cache_db.update_cache(cache_new).await? else {
+        return Ok(CacheEditResponse::new());
+    };
+
+    // Cache is updated, so failures below must not fail the request
+    let after = match cache_db.get_cache_by_id(oid).await {
+        Ok(after) => after,
+        Err(err) => {
+            tracing::error!(error = %err, cache_id = %oid, "failed to load edited cache");
+            None
+        }
+    };
+
+    let entry = AuditEntry::new(
+        auth.user_id,
+        AuditAction::Edit,
+        oid,
+        Some(&before),
+        after.as_ref(),
+        &request_id,
+    );
+    if let Err(err) = audit_db.record(entry).await {
+        tracing::error!(error = %err, cache_id = %oid, "failed to record cache edit");
     }
+    if let Some(after) = &after {
+        events.publish(CacheEventKind::Updated, after, Some(&before));
+    }
+
+    Ok(CacheEditResponse::new())
 }
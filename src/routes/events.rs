@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use rocket::{
+    response::stream::{Event, EventStream},
+    tokio::{
+        self,
+        sync::broadcast::error::RecvError,
+        time::{interval, MissedTickBehavior},
+    },
+    Shutdown, State,
+};
+
+use super::view::CacheViewParameters;
+use crate::{
+    error::ApiError,
+    events::{CacheEvent, EventBus, LastEventId},
+    rate_limit::ReadLimit,
+};
+
+/// Period of `heartbeat` events keeping idle connections open through proxies
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+fn to_sse(event: &CacheEvent) -> Event {
+    Event::json(event)
+        .event(event.kind.as_str())
+        .id(event.id.to_string())
+}
+
+/// Streams cache changes in the area as Server-Sent Events. Events missed
+/// since `Last-Event-ID` are replayed while they are kept in memory
+#[get("/events?<params..>")]
+pub async fn cache_events(
+    params: CacheViewParameters,
+    last_event_id: LastEventId,
+    events: &State<EventBus>,
+    _limit: ReadLimit,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![Event + 'static], ApiError> {
+    let bounds = params.get_bound_points();
+    if params.coordinates_provided() && bounds.is_none() {
+        return Err(ApiError::BadRequest(
+            "All bounds of search area are required".to_string(),
+        ));
+    }
+
+    let matches = move |event: &CacheEvent| bounds.as_ref().is_none_or(|b| event.is_within(b));
+    let (replay, mut receiver) = events.subscribe(last_event_id.0);
+
+    Ok(EventStream! {
+        for event in replay.iter().filter(|event| matches(event)) {
+            yield to_sse(event);
+        }
+
+        let mut heartbeat = interval(HEARTBEAT_INTERVAL);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        heartbeat.tick().await;
+
+        loop {
+            tokio::select! {
+                received = receiver.recv() => match received {
+                    Ok(event) if matches(&event) => yield to_sse(&event),
+                    Ok(_) => {}
+                    // Client reconnects with `Last-Event-ID` and gets missed events replayed
+                    Err(RecvError::Lagged(_)) | Err(RecvError::Closed) => break,
+                },
+                _ = heartbeat.tick() => yield Event::data("{}").event("heartbeat"),
+                _ = &mut shutdown => break,
+            }
+        }
+    }
+    .heartbeat(None))
+}
@@ -0,0 +1,51 @@
+use mongodb::bson::oid::ObjectId;
+use rocket::serde::json::Json;
+use serde::Serialize;
+
+use crate::{
+    auth::AuthInfo,
+    db::{AuditDatabase, AuditEntry, CacheDatabase},
+    error::ApiError,
+    rate_limit::ReadLimit,
+};
+
+#[derive(Debug, Serialize)]
+pub struct CacheHistory {
+    history: Vec<AuditEntry>,
+}
+
+#[derive(Debug, Responder)]
+pub struct CacheHistoryResponse(Json<CacheHistory>);
+
+impl From<CacheHistory> for CacheHistoryResponse {
+    fn from(v: CacheHistory) -> Self {
+        Self(Json(v))
+    }
+}
+
+/// Returns all recorded changes of cache.
+/// History of purged caches is available to moderators only
+#[get("/<id>/history")]
+pub async fn view_history(
+    id: String,
+    cache_db: CacheDatabase,
+    audit_db: AuditDatabase,
+    auth: AuthInfo,
+    _limit: ReadLimit,
+) -> Result<CacheHistoryResponse, ApiError> {
+    let Ok(oid) = ObjectId::parse_str(&id) else {
+        return Err(ApiError::WrongObjectID);
+    };
+
+    let cache = cache_db.get_any_cache_by_id(oid).await?;
+
+    let owner_id = cache.and_then(|c| c.owner_id);
+    if !auth.can_manage(owner_id) {
+        return Err(ApiError::Forbidden(
+            "Only owner or moderator can view cache history".to_string(),
+        ));
+    }
+
+    let history = audit_db.get_history(oid).await?;
+    Ok(CacheHistory { history }.into())
+}
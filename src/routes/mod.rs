@@ -1,8 +1,20 @@
+use mongodb::bson::oid::ObjectId;
 use rocket::{Build, Rocket};
 
+use crate::{
+    db::{parse_short_code, Cache, CacheDatabase},
+    error::ApiError,
+};
+
 mod create;
 use create::create_cache;
 
+mod batch;
+use batch::view_cache_batch;
+
+mod bulk;
+use bulk::bulk_caches;
+
 mod view;
 use view::view_cache;
 use view::view_caches;
@@ -10,8 +22,81 @@ use view::view_caches;
 mod delete;
 use delete::delete_cache;
 
+mod restore;
+use restore::restore_cache;
+
 mod edit;
 use edit::edit_cache;
+
+mod history;
+use history::view_history;
+
+mod events;
+use events::cache_events;
+
+mod changes;
+use changes::view_changes;
+
+mod webhooks;
+use webhooks::{create_webhook, delete_webhook, view_deliveries, view_webhooks};
+
+mod revisions;
+use revisions::{restore_revision, view_revision, view_revisions};
+
+/// Cache referenced in request by id or by short code
+enum CacheKey {
+    Id(ObjectId),
+    Code(String),
+}
+
+impl CacheKey {
+    fn parse(value: &str) -> Option<Self> {
+        match ObjectId::parse_str(value) {
+            Ok(id) => Some(Self::Id(id)),
+            Err(_) => parse_short_code(value).map(Self::Code),
+        }
+    }
+
+    fn matches(&self, cache: &Cache) -> bool {
+        match self {
+            Self::Id(id) => cache.id.as_ref() == Some(id),
+            Self::Code(code) => cache.code.as_ref() == Some(code),
+        }
+    }
+}
+
+/// Returns active caches referenced by keys, fetched in one query
+async fn find_caches<'a>(
+    cache_db: &CacheDatabase,
+    keys: impl Iterator<Item = &'a CacheKey>,
+) -> Result<Vec<Cache>, ApiError> {
+    let mut ids = Vec::new();
+    let mut codes = Vec::new();
+    for key in keys {
+        match key {
+            CacheKey::Id(id) => ids.push(*id),
+            CacheKey::Code(code) => codes.push(code.clone()),
+        }
+    }
+
+    if ids.is_empty() && codes.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(cache_db.get_caches_by_ids(&ids, &codes).await?)
+}
+
+/// Resolves cache given in path either by id or by short code
+async fn cache_id(cache_db: &CacheDatabase, id: &str) -> Result<ObjectId, ApiError> {
+    match CacheKey::parse(id) {
+        Some(CacheKey::Id(oid)) => Ok(oid),
+        Some(CacheKey::Code(code)) => cache_db
+            .get_cache_id_by_code(&code)
+            .await?
+            .ok_or_else(|| ApiError::NotFound("Cache not found".to_string())),
+        None => Err(ApiError::WrongObjectID),
+    }
+}
+
 pub trait RocketRoutesAdd {
     fn routes_add(self, api_base: &str) -> Self;
 }
@@ -25,8 +110,26 @@ impl RocketRoutesAdd for Rocket<Build> {
                 create_cache,
                 view_caches,
                 view_cache,
+                view_cache_batch,
+                bulk_caches,
                 delete_cache,
-                edit_cache
+                restore_cache,
+                edit_cache,
+                view_history,
+                view_revisions,
+                view_revision,
+                restore_revision,
+                cache_events,
+                view_changes
+            ],
+        )
+        .mount(
+            format!("{}/webhooks", api_base),
+            routes![
+                create_webhook,
+                view_webhooks,
+                delete_webhook,
+                view_deliveries
             ],
         )
     }
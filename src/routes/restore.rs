@@ -0,0 +1,73 @@
+use crate::{
+    auth::AuthInfo,
+    db::{AuditAction, AuditDatabase, AuditEntry, CacheDatabase},
+    error::ApiError,
+    events::{CacheEventKind, EventBus},
+    rate_limit::WriteLimit,
+    request_id::RequestId,
+};
+use mongodb::bson::oid::ObjectId;
+use rocket::{
+    serde::json::{Json, Value},
+    State,
+};
+use serde_json::json;
+
+#[derive(Debug, Responder)]
+pub struct CacheRestored(Json<Value>);
+impl CacheRestored {
+    pub fn new() -> Self {
+        Self(Json(json!({})))
+    }
+}
+
+fn deleted_not_found() -> ApiError {
+    ApiError::NotFound("Deleted cache not found".to_string())
+}
+
+/// Returns back soft deleted cache
+#[post("/<id>/restore")]
+pub async fn restore_cache(
+    id: String,
+    cache_db: CacheDatabase,
+    audit_db: AuditDatabase,
+    _limit: WriteLimit,
+    auth: AuthInfo,
+    request_id: RequestId,
+    events: &State<EventBus>,
+) -> Result<CacheRestored, ApiError> {
+    let Ok(oid) = ObjectId::parse_str(&id) else {
+        return Err(ApiError::WrongObjectID);
+    };
+
+    let deleted = match cache_db.get_any_cache_by_id(oid).await? {
+        Some(cache) if cache.deleted_at.is_some() => cache,
+        _ => return Err(deleted_not_found()),
+    };
+
+    if !auth.can_manage(deleted.owner_id) {
+        return Err(ApiError::Forbidden(
+            "Only owner or moderator can restore cache".to_string(),
+        ));
+    }
+
+    let Some(restored) = cache_db.restore_cache_by_id(oid).await? else {
+        return Err(deleted_not_found());
+    };
+
+    // Cache is restored, so failure here must not fail the request
+    let entry = AuditEntry::new(
+        auth.user_id,
+        AuditAction::Restore,
+        oid,
+        Some(&deleted),
+        Some(&restored),
+        &request_id,
+    );
+    if let Err(err) = audit_db.record(entry).await {
+        tracing::error!(error = %err, cache_id = %oid, "failed to record cache restore");
+    }
+    events.publish(CacheEventKind::Created, &restored, None);
+
+    Ok(CacheRestored::new())
+}
@@ -0,0 +1,156 @@
+use mongodb::bson::oid::ObjectId;
+use rocket::{serde::json::Json, State};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    auth::AuthInfo,
+    db::{AuditAction, AuditDatabase, AuditEntry, Cache, CacheDatabase, Revision},
+    error::ApiError,
+    events::{CacheEventKind, EventBus},
+    rate_limit::{ReadLimit, WriteLimit},
+    request_id::RequestId,
+};
+
+#[derive(Debug, Serialize)]
+pub struct RevisionsView {
+    revisions: Vec<Revision>,
+}
+
+#[derive(Debug, Responder)]
+pub struct RevisionsViewResponse(Json<RevisionsView>);
+
+impl From<RevisionsView> for RevisionsViewResponse {
+    fn from(v: RevisionsView) -> Self {
+        Self(Json(v))
+    }
+}
+
+#[derive(Debug, Responder)]
+pub struct RevisionRestoredResponse(Json<Value>);
+impl RevisionRestoredResponse {
+    pub fn new() -> Self {
+        Self(Json(json!({})))
+    }
+}
+
+fn revision_not_found() -> ApiError {
+    ApiError::NotFound("Revision not found".to_string())
+}
+
+fn cache_not_found() -> ApiError {
+    ApiError::NotFound("Cache not found".to_string())
+}
+
+/// Revisions are visible while cache is, so deleted caches hide them too
+async fn active_cache(cache_db: &CacheDatabase, id: ObjectId) -> Result<Cache, ApiError> {
+    cache_db
+        .get_cache_by_id(id)
+        .await?
+        .ok_or_else(cache_not_found)
+}
+
+#[get("/<id>/revisions")]
+pub async fn view_revisions(
+    id: String,
+    cache_db: CacheDatabase,
+    _limit: ReadLimit,
+) -> Result<RevisionsViewResponse, ApiError> {
+    let Ok(oid) = ObjectId::parse_str(&id) else {
+        return Err(ApiError::WrongObjectID);
+    };
+
+    active_cache(&cache_db, oid).await?;
+    let revisions = cache_db.get_revisions(oid).await?;
+    Ok(RevisionsView { revisions }.into())
+}
+
+#[get("/<id>/revisions/<number>")]
+pub async fn view_revision(
+    id: String,
+    number: u32,
+    cache_db: CacheDatabase,
+    _limit: ReadLimit,
+) -> Result<RevisionsViewResponse, ApiError> {
+    let Ok(oid) = ObjectId::parse_str(&id) else {
+        return Err(ApiError::WrongObjectID);
+    };
+
+    active_cache(&cache_db, oid).await?;
+    let Some(revision) = cache_db.get_revision(oid, number).await? else {
+        return Err(revision_not_found());
+    };
+
+    Ok(RevisionsView {
+        revisions: vec![revision],
+    }
+    .into())
+}
+
+/// Replaces cache content with one of its revisions.
+/// Current content is kept as a new revision so history is never rewritten
+#[post("/<id>/revisions/<number>/restore")]
+pub async fn restore_revision(
+    id: String,
+    number: u32,
+    cache_db: CacheDatabase,
+    audit_db: AuditDatabase,
+    _limit: WriteLimit,
+    auth: AuthInfo,
+    request_id: RequestId,
+    events: &State<EventBus>,
+) -> Result<RevisionRestoredResponse, ApiError> {
+    let Ok(oid) = ObjectId::parse_str(&id) else {
+        return Err(ApiError::WrongObjectID);
+    };
+
+    let current = active_cache(&cache_db, oid).await?;
+
+    if current.owner_id != Some(auth.user_id) {
+        return Err(ApiError::Forbidden(
+            "Only owner can restore cache revision".to_string(),
+        ));
+    }
+
+    let Some(revision) = cache_db.get_revision(oid, number).await? else {
+        return Err(revision_not_found());
+    };
+
+    let mut restored = revision.cache;
+    restored.id = Some(oid);
+    // Revisions made before caches had names keep the current one
+    if restored.name.is_empty() {
+        restored.name = current.name.clone();
+    }
+
+    // Cache may be deleted since it was checked
+    let Some(before) = cache_db.update_cache(restored).await? else {
+        return Err(cache_not_found());
+    };
+
+    // Cache is updated, so failures below must not fail the request
+    let after = match cache_db.get_cache_by_id(oid).await {
+        Ok(after) => after,
+        Err(err) => {
+            tracing::error!(error = %err, cache_id = %oid, "failed to load reverted cache");
+            None
+        }
+    };
+
+    let entry = AuditEntry::new(
+        auth.user_id,
+        AuditAction::Revert,
+        oid,
+        Some(&before),
+        after.as_ref(),
+        &request_id,
+    );
+    if let Err(err) = audit_db.record(entry).await {
+        tracing::error!(error = %err, cache_id = %oid, "failed to record cache revert");
+    }
+    if let Some(after) = &after {
+        events.publish(CacheEventKind::Updated, after, Some(&before));
+    }
+
+    Ok(RevisionRestoredResponse::new())
+}
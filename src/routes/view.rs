@@ -1,12 +1,19 @@
-use mongodb::bson::oid::ObjectId;
 use rocket::serde::json::Json;
 use serde::{Deserialize, Serialize};
 
+use super::cache_id;
 use crate::{
-    db::{Cache, CacheDatabase, DatabaseErrorResponse, LatLong},
-    status::ResponseError,
+    db::{
+        Cache, CacheDatabase, CacheSort, CacheSortKey, LatLong, ScoredCache, LIST_FIELDS,
+        PUBLIC_FIELDS,
+    },
+    error::ApiError,
+    rate_limit::ReadLimit,
 };
 
+/// Longest accepted text search query
+const MAX_QUERY_LENGTH: usize = 200;
+
 #[derive(Serialize, Deserialize, FromForm)]
 pub struct CacheViewParameters {
     pub user_id: Option<i32>,
@@ -16,6 +23,21 @@ pub struct CacheViewParameters {
 
     pub min_long: Option<f64>,
     pub max_long: Option<f64>,
+
+    /// Text to search in cache texts. Found caches are ordered by relevance
+    pub q: Option<String>,
+
+    /// Comma separated fields of listed caches or `full` for all of them.
+    /// Id and position are always returned
+    pub fields: Option<String>,
+
+    /// Order of list: `created_at`, `updated_at` or `distance`,
+    /// prefixed with `-` for descending order
+    pub sort: Option<String>,
+
+    /// Point used to sort by distance
+    pub near_lat: Option<f64>,
+    pub near_long: Option<f64>,
 }
 
 impl CacheViewParameters {
@@ -33,6 +55,86 @@ impl CacheViewParameters {
             && self.max_long.is_some()
     }
 
+    /// Search query without surrounding whitespace, if not empty
+    pub fn query(&self) -> Option<&str> {
+        self.q.as_deref().map(str::trim).filter(|q| !q.is_empty())
+    }
+
+    /// Fields requested for listed caches
+    pub fn list_fields(&self) -> Result<Vec<&'static str>, ApiError> {
+        let Some(fields) = self.fields.as_deref().map(str::trim) else {
+            return Ok(LIST_FIELDS.to_vec());
+        };
+        if fields == "full" {
+            return Ok(PUBLIC_FIELDS.to_vec());
+        }
+
+        let mut selected = Vec::new();
+        for field in fields.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            // Id is returned anyway, so asking for it is not an error
+            if field == "id" || field == "_id" {
+                continue;
+            }
+
+            let Some(field) = PUBLIC_FIELDS.iter().find(|public| **public == field) else {
+                return Err(ApiError::BadRequest(format!(
+                    "Unknown field '{}', available: {}",
+                    field,
+                    PUBLIC_FIELDS.join(", ")
+                )));
+            };
+            if !selected.contains(field) {
+                selected.push(*field);
+            }
+        }
+        Ok(selected)
+    }
+
+    /// Requested order of list
+    pub fn list_sort(&self) -> Result<Option<CacheSort>, ApiError> {
+        let Some(sort) = self
+            .sort
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        else {
+            return Ok(None);
+        };
+        let (name, descending) = match sort.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (sort, false),
+        };
+
+        let key = match name {
+            "created_at" => CacheSortKey::CreatedAt,
+            "updated_at" => CacheSortKey::UpdatedAt,
+            "distance" => {
+                let (Some(lat), Some(lng)) = (self.near_lat, self.near_long) else {
+                    return Err(ApiError::BadRequest(
+                        "Sorting by distance requires near_lat and near_long".to_string(),
+                    ));
+                };
+                CacheSortKey::Distance(LatLong { lat, lng })
+            }
+            // Caches keep no difficulty, terrain or finds to order by
+            "difficulty" | "terrain" | "find_count" => {
+                return Err(ApiError::BadRequest(format!(
+                    "Sorting by {} is not available as caches do not store it, \
+                     available: created_at, updated_at, distance",
+                    name
+                )))
+            }
+            _ => {
+                return Err(ApiError::BadRequest(format!(
+                    "Unknown sort '{}', available: created_at, updated_at, distance",
+                    name
+                )))
+            }
+        };
+
+        Ok(Some(CacheSort { key, descending }))
+    }
+
     pub fn get_bound_points(&self) -> Option<(LatLong, LatLong)> {
         if !self.coordinates_provided() {
             return None;
@@ -69,37 +171,26 @@ pub struct CacheView {
     caches: Vec<Cache>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct CacheSearchView {
+    caches: Vec<ScoredCache>,
+}
+
 #[derive(Debug, Responder)]
-pub struct CacheViewResponse(Json<CacheView>);
+pub enum CacheViewResponse {
+    List(Json<CacheView>),
+    Search(Json<CacheSearchView>),
+}
 
 impl From<CacheView> for CacheViewResponse {
     fn from(v: CacheView) -> Self {
-        Self(Json(v))
+        Self::List(Json(v))
     }
 }
 
-pub enum CacheViewErrors {
-    IncompleteBoundSpecification,
-    DatabaseError(mongodb::error::Error),
-}
-
-#[derive(Debug, Responder)]
-pub enum CacheViewErrorResponse {
-    #[response(status = 400)]
-    IncompleteBoundSpecification(Json<ResponseError>),
-    DBError(DatabaseErrorResponse),
-}
-
-impl From<CacheViewErrors> for CacheViewErrorResponse {
-    fn from(err: CacheViewErrors) -> Self {
-        match err {
-            CacheViewErrors::IncompleteBoundSpecification => {
-                Self::IncompleteBoundSpecification(Json(ResponseError::new(
-                    "Необходимо задать все границы области поиска".to_string(),
-                )))
-            }
-            CacheViewErrors::DatabaseError(e) => Self::DBError(DatabaseErrorResponse::new(e)),
-        }
+impl From<CacheSearchView> for CacheViewResponse {
+    fn from(v: CacheSearchView) -> Self {
+        Self::Search(Json(v))
     }
 }
 
@@ -107,28 +198,56 @@ impl From<CacheViewErrors> for CacheViewErrorResponse {
 pub async fn view_caches(
     params: CacheViewParameters,
     cache_db: CacheDatabase,
-) -> Result<CacheViewResponse, CacheViewErrorResponse> {
+    _limit: ReadLimit,
+) -> Result<CacheViewResponse, ApiError> {
     let bounds = params.get_bound_points();
     // If coords provided but we cannot create bounds it means that not all coordiantes provided
     if params.coordinates_provided() && bounds.is_none() {
-        return Err(CacheViewErrors::IncompleteBoundSpecification.into());
+        return Err(ApiError::BadRequest(
+            "All bounds of search area are required".to_string(),
+        ));
     }
 
-    match cache_db.get_caches(params.user_id, bounds).await {
-        Ok(caches) => Ok(CacheView { caches }.into()),
-        Err(err) => Err(CacheViewErrors::DatabaseError(err).into()),
+    let fields = params.list_fields()?;
+    let sort = params.list_sort()?;
+
+    if let Some(query) = params.query() {
+        // Search results are ordered by relevance
+        if sort.is_some() {
+            return Err(ApiError::BadRequest(
+                "Search results cannot be sorted".to_string(),
+            ));
+        }
+
+        if query.chars().count() > MAX_QUERY_LENGTH {
+            return Err(ApiError::BadRequest(format!(
+                "Search query is longer than {} characters",
+                MAX_QUERY_LENGTH
+            )));
+        }
+
+        let caches = cache_db
+            .search_caches(query, params.user_id, bounds, &fields)
+            .await?;
+        return Ok(CacheSearchView { caches }.into());
     }
+
+    let caches = cache_db
+        .get_caches(params.user_id, bounds, &fields, sort.as_ref())
+        .await?;
+    Ok(CacheView { caches }.into())
 }
 
 #[get("/<id>")]
-pub async fn view_cache(id: String, cache_db: CacheDatabase) -> Option<CacheViewResponse> {
-    let Ok(oid) = ObjectId::parse_str(&id) else {
-        return None;
-    };
-
-    match cache_db.get_cache_by_id(oid).await {
-        Ok(Some(c)) => Some(CacheView { caches: vec![c] }.into()),
-        Err(_) => None,
-        _ => None,
+pub async fn view_cache(
+    id: String,
+    cache_db: CacheDatabase,
+    _limit: ReadLimit,
+) -> Result<CacheViewResponse, ApiError> {
+    let oid = cache_id(&cache_db, &id).await?;
+
+    match cache_db.get_cache_by_id(oid).await? {
+        Some(c) => Ok(CacheView { caches: vec![c] }.into()),
+        None => Err(ApiError::NotFound("Cache not found".to_string())),
     }
 }
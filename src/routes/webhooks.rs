@@ -0,0 +1,186 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use rocket::serde::json::{Json, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    auth::AuthInfo,
+    db::{WebhookDatabase, WebhookDelivery, WebhookSubscription},
+    error::ApiError,
+    events::CacheEventKind,
+    rate_limit::{ReadLimit, WriteLimit},
+};
+
+/// Delivery attempts returned for subscription
+const DELIVERIES_LIMIT: i64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct NewWebhook {
+    url: String,
+    secret: String,
+    events: Vec<CacheEventKind>,
+}
+
+/// Subscription as shown to moderators, without secret
+#[derive(Debug, Serialize)]
+pub struct WebhookView {
+    id: Option<ObjectId>,
+    url: String,
+    events: Vec<CacheEventKind>,
+    created_by: i32,
+    created_at: DateTime,
+}
+
+impl From<WebhookSubscription> for WebhookView {
+    fn from(s: WebhookSubscription) -> Self {
+        Self {
+            id: s.id,
+            url: s.url,
+            events: s.events,
+            created_by: s.created_by,
+            created_at: s.created_at,
+        }
+    }
+}
+
+#[derive(Responder)]
+#[response(status = 201)]
+pub struct WebhookAdded(Json<Value>);
+
+#[derive(Debug, Responder)]
+pub struct WebhookDeleted(Json<Value>);
+
+#[derive(Debug, Serialize)]
+pub struct Webhooks {
+    webhooks: Vec<WebhookView>,
+}
+
+#[derive(Debug, Responder)]
+pub struct WebhooksViewResponse(Json<Webhooks>);
+
+impl From<Webhooks> for WebhooksViewResponse {
+    fn from(v: Webhooks) -> Self {
+        Self(Json(v))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Deliveries {
+    deliveries: Vec<WebhookDelivery>,
+}
+
+#[derive(Debug, Responder)]
+pub struct DeliveriesViewResponse(Json<Deliveries>);
+
+impl From<Deliveries> for DeliveriesViewResponse {
+    fn from(v: Deliveries) -> Self {
+        Self(Json(v))
+    }
+}
+
+/// Webhooks receive changes of all caches, so only moderators manage them
+fn require_moderator(auth: &AuthInfo) -> Result<(), ApiError> {
+    if auth.is_moderator {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(
+            "Only moderators can manage webhooks".to_string(),
+        ))
+    }
+}
+
+#[post("/", format = "json", data = "<webhook>")]
+pub async fn create_webhook(
+    webhook: Json<NewWebhook>,
+    webhook_db: WebhookDatabase,
+    _limit: WriteLimit,
+    auth: AuthInfo,
+) -> Result<WebhookAdded, ApiError> {
+    require_moderator(&auth)?;
+
+    let webhook = webhook.0;
+    match reqwest::Url::parse(&webhook.url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+        _ => {
+            return Err(ApiError::BadRequest(
+                "Webhook url must be an http(s) URL".to_string(),
+            ))
+        }
+    }
+    if webhook.secret.is_empty() {
+        return Err(ApiError::BadRequest(
+            "Webhook secret must not be empty".to_string(),
+        ));
+    }
+    if webhook.events.is_empty() {
+        return Err(ApiError::BadRequest(
+            "At least one event type required".to_string(),
+        ));
+    }
+
+    let subscription = WebhookSubscription {
+        id: None,
+        url: webhook.url,
+        secret: webhook.secret,
+        events: webhook.events,
+        created_by: auth.user_id,
+        created_at: DateTime::now(),
+    };
+    let id = webhook_db.insert_subscription(subscription).await?;
+
+    Ok(WebhookAdded(Json(json!({ "id": id }))))
+}
+
+#[get("/")]
+pub async fn view_webhooks(
+    webhook_db: WebhookDatabase,
+    auth: AuthInfo,
+    _limit: ReadLimit,
+) -> Result<WebhooksViewResponse, ApiError> {
+    require_moderator(&auth)?;
+
+    let webhooks = webhook_db
+        .get_subscriptions()
+        .await?
+        .into_iter()
+        .map(WebhookView::from)
+        .collect();
+    Ok(Webhooks { webhooks }.into())
+}
+
+#[delete("/<id>")]
+pub async fn delete_webhook(
+    id: String,
+    webhook_db: WebhookDatabase,
+    _limit: WriteLimit,
+    auth: AuthInfo,
+) -> Result<WebhookDeleted, ApiError> {
+    require_moderator(&auth)?;
+
+    let Ok(oid) = ObjectId::parse_str(&id) else {
+        return Err(ApiError::WrongObjectID);
+    };
+
+    if !webhook_db.delete_subscription(oid).await? {
+        return Err(ApiError::NotFound("Webhook not found".to_string()));
+    }
+    Ok(WebhookDeleted(Json(json!({}))))
+}
+
+/// Returns latest delivery attempts, newest first
+#[get("/<id>/deliveries")]
+pub async fn view_deliveries(
+    id: String,
+    webhook_db: WebhookDatabase,
+    auth: AuthInfo,
+    _limit: ReadLimit,
+) -> Result<DeliveriesViewResponse, ApiError> {
+    require_moderator(&auth)?;
+
+    let Ok(oid) = ObjectId::parse_str(&id) else {
+        return Err(ApiError::WrongObjectID);
+    };
+
+    let deliveries = webhook_db.get_deliveries(oid, DELIVERIES_LIMIT).await?;
+    Ok(Deliveries { deliveries }.into())
+}
@@ -1,12 +1,46 @@
+use rocket::{
+    http::{ContentType, Status},
+    response::{self, Responder},
+    serde::json::Json,
+    Request, Response,
+};
 use serde::Serialize;
 
+use crate::request_id::RequestId;
+
+/// Error description in RFC 7807 `application/problem+json` format
 #[derive(Debug, Serialize)]
-pub struct ResponseError {
-    message: String,
+pub struct Problem {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'static str,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    instance: String,
+    /// Id of request to find its details in server logs
+    correlation_id: String,
+}
+
+impl Problem {
+    pub fn new(req: &Request<'_>, status: Status, detail: Option<String>) -> Self {
+        Self {
+            problem_type: "about:blank",
+            title: status.reason_lossy(),
+            status: status.code,
+            detail,
+            instance: req.uri().path().to_string(),
+            correlation_id: RequestId::of(req).0.clone(),
+        }
+    }
 }
 
-impl ResponseError {
-    pub fn new(error_msg: String) -> Self {
-        Self { message: error_msg }
+impl<'r> Responder<'r, 'static> for Problem {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let status = Status::new(self.status);
+        Response::build_from(Json(self).respond_to(req)?)
+            .status(status)
+            .header(ContentType::new("application", "problem+json"))
+            .ok()
     }
 }
@@ -0,0 +1,171 @@
+use std::{sync::Arc, time::Duration};
+
+use hmac::{Hmac, Mac};
+use mongodb::bson::DateTime;
+use reqwest::Client;
+use rocket::{
+    fairing::AdHoc,
+    tokio::{self, sync::mpsc},
+    Build, Rocket,
+};
+use serde_json::json;
+use sha2::Sha256;
+
+use crate::{
+    db::{WebhookDelivery, WebhookStore, WebhookSubscription},
+    events::{CacheEvent, EventBus},
+};
+
+/// Header with `sha256=<hex>` HMAC of request body made with subscription secret
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+
+/// Attempts made to deliver each event before giving up
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay before second attempt, doubled for each next one
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub trait RocketWebhooksAdd {
+    fn webhooks_add(self) -> Self;
+}
+
+impl RocketWebhooksAdd for Rocket<Build> {
+    /// Starts background task sending published cache events to subscribed webhooks
+    fn webhooks_add(self) -> Self {
+        self.attach(AdHoc::on_liftoff("Webhook dispatcher", |rocket| {
+            Box::pin(async move {
+                let store = rocket
+                    .state::<Arc<dyn WebhookStore>>()
+                    .expect("Database must be connected before webhooks")
+                    .clone();
+                let events = rocket
+                    .state::<EventBus>()
+                    .expect("Events must be added before webhooks");
+
+                tokio::spawn(dispatch_loop(store, events.queue()));
+            })
+        }))
+    }
+}
+
+async fn dispatch_loop(
+    store: Arc<dyn WebhookStore>,
+    mut receiver: mpsc::UnboundedReceiver<CacheEvent>,
+) {
+    let client = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("Failed to create webhook client");
+
+    while let Some(event) = receiver.recv().await {
+        let subscriptions = match store.get_subscriptions().await {
+            Ok(subscriptions) => subscriptions,
+            Err(err) => {
+                tracing::error!(error = %err, "failed to load webhook subscriptions");
+                continue;
+            }
+        };
+
+        let payload = json!({
+            "id": event.uid.to_hex(),
+            "type": event.kind,
+            "cache_id": event.cache_id.to_hex(),
+            "position": event.position,
+            "previous_position": event.previous_position,
+            "timestamp": event.timestamp.try_to_rfc3339_string().unwrap_or_default(),
+        })
+        .to_string();
+
+        for subscription in subscriptions
+            .into_iter()
+            .filter(|s| s.events.contains(&event.kind))
+        {
+            tokio::spawn(deliver(
+                client.clone(),
+                store.clone(),
+                subscription,
+                event.clone(),
+                payload.clone(),
+            ));
+        }
+    }
+}
+
+/// Returns `sha256=<hex>` signature of payload
+pub fn sign(secret: &str, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts key of any size");
+    mac.update(payload.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Sends event to subscription retrying with exponential backoff.
+/// Every attempt is recorded in delivery log
+async fn deliver(
+    client: Client,
+    store: Arc<dyn WebhookStore>,
+    subscription: WebhookSubscription,
+    event: CacheEvent,
+    payload: String,
+) {
+    let Some(subscription_id) = subscription.id else {
+        return;
+    };
+    let signature = sign(&subscription.secret, &payload);
+    let mut backoff = INITIAL_BACKOFF;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let result = client
+            .post(&subscription.url)
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header(EVENT_HEADER, event.kind.as_str())
+            .body(payload.clone())
+            .send()
+            .await;
+
+        let (status, error) = match result {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16()), None)
+            }
+            Ok(response) => (
+                Some(response.status().as_u16()),
+                Some("endpoint answered with error status".to_string()),
+            ),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        let success = error.is_none();
+
+        let delivery = WebhookDelivery {
+            id: None,
+            subscription_id,
+            event_id: event.uid,
+            event: event.kind,
+            attempt,
+            success,
+            status,
+            error,
+            timestamp: DateTime::now(),
+        };
+        if let Err(err) = store.record_delivery(delivery).await {
+            tracing::error!(error = %err, "failed to record webhook delivery");
+        }
+
+        if success {
+            return;
+        }
+
+        if attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    tracing::warn!(
+        subscription_id = %subscription_id,
+        url = %subscription.url,
+        event_id = %event.uid,
+        "webhook delivery failed"
+    );
+}
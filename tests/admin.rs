@@ -0,0 +1,40 @@
+//! Maintenance operations of `msd-cache-admin`. They need running MongoDB,
+//! so tests are ignored unless run with `--ignored` and `MONGODB_TEST_URL`
+
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    Client,
+};
+use msd_cache_service::db::AdminDatabase;
+use rocket::futures::TryStreamExt;
+
+#[rocket::async_test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URL"]
+async fn imported_caches_without_code_get_new_codes() {
+    let url = std::env::var("MONGODB_TEST_URL")
+        .expect("MONGODB_TEST_URL must be set to run MongoDB tests");
+    let database = Client::with_uri_str(&url)
+        .await
+        .expect("Invalid MONGODB_TEST_URL")
+        .database(&format!("msd_cache_test_{}", ObjectId::new()));
+    let admin = AdminDatabase::new(database.clone());
+
+    for name in ["First", "Second"] {
+        let cache = doc! { "name": name, "position": { "lat": 1.0, "lng": 2.0 } };
+        admin.import(cache).await.unwrap();
+    }
+
+    let caches: Vec<Document> = database
+        .collection("cache")
+        .find(None, None)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    database.drop(None).await.unwrap();
+
+    let codes: Vec<_> = caches.iter().map(|c| c.get_str("code").unwrap()).collect();
+    assert_eq!(codes.len(), 2);
+    assert_ne!(codes[0], codes[1]);
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use common::{
+    auth, basic, client, client_with, start_login_stub, ALICE, BROKEN, GARBLED, PASSWORD,
+};
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::asynchronous::{Client, LocalRequest},
+};
+use serde_json::{json, Value};
+
+fn create_request(client: &Client) -> LocalRequest<'_> {
+    client
+        .post("/api/v1/cache")
+        .header(ContentType::JSON)
+        .body(json!({ "name": "Bench", "position": { "lat": 1.0, "lng": 2.0 } }).to_string())
+}
+
+/// Dispatches request and checks status and problem detail
+async fn assert_problem(request: LocalRequest<'_>, status: Status, detail: &str) {
+    let response = request.dispatch().await;
+    assert_eq!(response.status(), status);
+
+    let body: Value = response.into_json().await.unwrap();
+    assert_eq!(body["status"], status.code);
+    assert_eq!(body["detail"], detail);
+}
+
+#[rocket::async_test]
+async fn valid_credentials_are_accepted() {
+    let client = client().await;
+    let response = create_request(&client).header(auth(ALICE)).dispatch().await;
+    assert_eq!(response.status(), Status::Created);
+}
+
+#[rocket::async_test]
+async fn missing_header_is_unauthorized() {
+    let client = client().await;
+    assert_problem(
+        create_request(&client),
+        Status::Unauthorized,
+        "Authorization header required",
+    )
+    .await;
+}
+
+#[rocket::async_test]
+async fn unsupported_scheme_is_not_implemented() {
+    let client = client().await;
+    assert_problem(
+        create_request(&client).header(Header::new("Authorization", "Bearer token")),
+        Status::NotImplemented,
+        "Only Basic authorization is supported",
+    )
+    .await;
+}
+
+#[rocket::async_test]
+async fn several_headers_are_rejected() {
+    let client = client().await;
+    assert_problem(
+        create_request(&client)
+            .header(auth(ALICE))
+            .header(auth(ALICE)),
+        Status::BadRequest,
+        "Only one Authorization header allowed",
+    )
+    .await;
+}
+
+#[rocket::async_test]
+async fn malformed_header_is_rejected() {
+    let client = client().await;
+    for value in ["Basic", "Basic not-base64!", "Basic bm8tY29sb24="] {
+        assert_problem(
+            create_request(&client).header(Header::new("Authorization", value)),
+            Status::BadRequest,
+            "Invalid Authorization header format",
+        )
+        .await;
+    }
+}
+
+#[rocket::async_test]
+async fn wrong_password_is_unauthorized() {
+    let client = client().await;
+    assert_problem(
+        create_request(&client).header(basic(ALICE.0, "wrong")),
+        Status::Unauthorized,
+        "Invalid credentials",
+    )
+    .await;
+}
+
+#[rocket::async_test]
+async fn repeated_failures_are_limited() {
+    let client = client().await;
+    for _ in 0..10 {
+        let response = create_request(&client)
+            .header(basic(ALICE.0, "wrong"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    // Even valid credentials are not checked until budget refills
+    let response = create_request(&client)
+        .header(basic(ALICE.0, PASSWORD))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::TooManyRequests);
+    assert!(response.headers().get_one("Retry-After").is_some());
+}
+
+#[rocket::async_test]
+async fn login_service_failures_are_unavailable() {
+    let client = client().await;
+    // Outages must not count as failed attempts of client
+    for _ in 0..10 {
+        for email in [GARBLED, BROKEN] {
+            assert_problem(
+                create_request(&client).header(basic(email, PASSWORD)),
+                Status::ServiceUnavailable,
+                "Login service is unavailable, try again later",
+            )
+            .await;
+        }
+    }
+
+    let response = create_request(&client).header(auth(ALICE)).dispatch().await;
+    assert_eq!(response.status(), Status::Created);
+}
+
+#[rocket::async_test]
+async fn login_service_address_may_omit_trailing_slash() {
+    let address = start_login_stub();
+    let address = address.trim_end_matches('/').to_string();
+    let client = client_with(|figment| figment.merge(("login_service_address", address))).await;
+
+    let response = create_request(&client).header(auth(ALICE)).dispatch().await;
+    assert_eq!(response.status(), Status::Created);
+}
+
+/// Dispatches request with wrong password from given client IP
+async fn fail_from(client: &Client, ip: usize) -> Status {
+    create_request(client)
+        .header(basic(ALICE.0, "wrong"))
+        .header(Header::new("X-Real-IP", format!("10.0.0.{}", ip)))
+        .dispatch()
+        .await
+        .status()
+}
+
+#[rocket::async_test]
+async fn ip_header_is_not_trusted_by_default() {
+    let client = client().await;
+    for ip in 0..10 {
+        assert_eq!(fail_from(&client, ip).await, Status::Unauthorized);
+    }
+    assert_eq!(fail_from(&client, 10).await, Status::TooManyRequests);
+}
+
+#[rocket::async_test]
+async fn configured_ip_header_is_trusted() {
+    let client = client_with(|figment| figment.merge(("trusted_ip_header", "X-Real-IP"))).await;
+    for ip in 0..20 {
+        assert_eq!(fail_from(&client, ip).await, Status::Unauthorized);
+    }
+}
@@ -0,0 +1,611 @@
+mod common;
+
+use common::{auth, client, create_cache, get_caches, ALICE, BOB, MODERATOR};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::{json, Value};
+
+#[rocket::async_test]
+async fn created_cache_can_be_viewed() {
+    let client = client().await;
+    let id = create_cache(
+        &client,
+        ALICE,
+        json!({
+            "position": { "lat": 55.75, "lng": 37.62 },
+            "description": "Under the bench",
+            "hint": "Look down",
+        }),
+    )
+    .await;
+
+    let caches = get_caches(&client, &format!("/api/v1/cache/{}", id)).await;
+    assert_eq!(caches.len(), 1);
+    assert_eq!(caches[0]["description"], "Under the bench");
+    assert_eq!(caches[0]["hint"], "Look down");
+    assert_eq!(caches[0]["owner_id"], ALICE.1);
+}
+
+#[rocket::async_test]
+async fn owner_is_taken_from_credentials() {
+    let client = client().await;
+    let id = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 }, "owner_id": BOB.1 }),
+    )
+    .await;
+
+    let caches = get_caches(&client, &format!("/api/v1/cache/{}", id)).await;
+    assert_eq!(caches[0]["owner_id"], ALICE.1);
+}
+
+#[rocket::async_test]
+async fn list_contains_only_summary() {
+    let client = client().await;
+    let id = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 }, "description": "Secret" }),
+    )
+    .await;
+
+    let caches = get_caches(&client, "/api/v1/cache").await;
+    assert_eq!(caches.len(), 1);
+    assert_eq!(caches[0]["_id"]["$oid"], id.as_str());
+    assert_eq!(caches[0]["position"], json!({ "lat": 1.0, "lng": 2.0 }));
+    assert_eq!(caches[0]["name"], "Test cache");
+    assert!(caches[0].get("description").is_none());
+}
+
+#[rocket::async_test]
+async fn list_returns_requested_fields() {
+    let client = client().await;
+    create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 }, "description": "Secret", "hint": "Look up" }),
+    )
+    .await;
+
+    let caches = get_caches(&client, "/api/v1/cache?fields=description,id").await;
+    assert_eq!(caches[0]["description"], "Secret");
+    assert_eq!(caches[0]["position"], json!({ "lat": 1.0, "lng": 2.0 }));
+    assert!(caches[0].get("_id").is_some());
+    assert!(caches[0].get("name").is_none());
+    assert!(caches[0].get("hint").is_none());
+
+    let caches = get_caches(&client, "/api/v1/cache?fields=full").await;
+    assert_eq!(caches[0]["hint"], "Look up");
+    assert_eq!(caches[0]["owner_id"], ALICE.1);
+    assert!(caches[0].get("language").is_none());
+
+    let response = client
+        .get("/api/v1/cache?fields=deleted_at")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn list_is_sorted_by_distance() {
+    let client = client().await;
+    for lat in [3.0, 1.0, 2.0] {
+        create_cache(
+            &client,
+            ALICE,
+            json!({ "position": { "lat": lat, "lng": 0.0 } }),
+        )
+        .await;
+    }
+
+    let latitudes = |caches: Vec<Value>| -> Vec<f64> {
+        caches
+            .iter()
+            .map(|cache| cache["position"]["lat"].as_f64().unwrap())
+            .collect()
+    };
+
+    let uri = "/api/v1/cache?sort=distance&near_lat=0&near_long=0";
+    assert_eq!(latitudes(get_caches(&client, uri).await), [1.0, 2.0, 3.0]);
+    let uri = "/api/v1/cache?sort=-distance&near_lat=0&near_long=0";
+    assert_eq!(latitudes(get_caches(&client, uri).await), [3.0, 2.0, 1.0]);
+
+    for uri in ["/api/v1/cache?sort=distance", "/api/v1/cache?sort=unknown"] {
+        let response = client.get(uri).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    // Fields which caches do not store
+    for key in ["difficulty", "terrain", "find_count"] {
+        let uri = format!("/api/v1/cache?sort=-{}", key);
+        let response = client.get(uri).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let body: Value = response.into_json().await.unwrap();
+        let detail = body["detail"].as_str().unwrap();
+        assert!(detail.contains("do not store"), "{}", detail);
+    }
+}
+
+#[rocket::async_test]
+async fn list_is_filtered_by_user() {
+    let client = client().await;
+    create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 1.0 } }),
+    )
+    .await;
+    create_cache(
+        &client,
+        BOB,
+        json!({ "position": { "lat": 2.0, "lng": 2.0 } }),
+    )
+    .await;
+
+    let caches = get_caches(&client, &format!("/api/v1/cache?user_id={}", BOB.1)).await;
+    assert_eq!(caches.len(), 1);
+    assert_eq!(caches[0]["position"]["lat"], 2.0);
+}
+
+#[rocket::async_test]
+async fn list_is_filtered_by_bounds() {
+    let client = client().await;
+    for (lat, lng) in [(10.0, 10.0), (20.0, 20.0), (30.0, 30.0)] {
+        create_cache(
+            &client,
+            ALICE,
+            json!({ "position": { "lat": lat, "lng": lng } }),
+        )
+        .await;
+    }
+
+    let caches = get_caches(
+        &client,
+        "/api/v1/cache?min_lat=15&max_lat=30&min_long=15&max_long=30",
+    )
+    .await;
+    assert_eq!(caches.len(), 2);
+
+    // Bounds given in wrong order are swapped
+    let caches = get_caches(
+        &client,
+        "/api/v1/cache?min_lat=25&max_lat=15&min_long=25&max_long=15",
+    )
+    .await;
+    assert_eq!(caches.len(), 1);
+    assert_eq!(caches[0]["position"]["lat"], 20.0);
+}
+
+#[rocket::async_test]
+async fn incomplete_bounds_are_rejected() {
+    let client = client().await;
+    let response = client
+        .get("/api/v1/cache?min_lat=15&max_lat=30&min_long=15")
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(
+        response.content_type(),
+        Some(ContentType::new("application", "problem+json"))
+    );
+}
+
+#[rocket::async_test]
+async fn edited_cache_keeps_previous_version() {
+    let client = client().await;
+    let id = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 }, "description": "Old" }),
+    )
+    .await;
+
+    let response = client
+        .put(format!("/api/v1/cache/{}", id))
+        .header(ContentType::JSON)
+        .header(auth(ALICE))
+        .body(json!({ "name": "Test cache", "position": { "lat": 3.0, "lng": 4.0 }, "description": "New" }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let caches = get_caches(&client, &format!("/api/v1/cache/{}", id)).await;
+    assert_eq!(caches[0]["description"], "New");
+    assert_eq!(caches[0]["position"], json!({ "lat": 3.0, "lng": 4.0 }));
+
+    let response = client
+        .get(format!("/api/v1/cache/{}/revisions", id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let body: Value = response.into_json().await.unwrap();
+    assert_eq!(body["revisions"][0]["revision"], 1);
+    assert_eq!(body["revisions"][0]["cache"]["description"], "Old");
+}
+
+#[rocket::async_test]
+async fn deleted_cache_is_hidden() {
+    let client = client().await;
+    let id = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 } }),
+    )
+    .await;
+
+    let response = client
+        .delete(format!("/api/v1/cache/{}", id))
+        .header(auth(ALICE))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client.get(format!("/api/v1/cache/{}", id)).dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+    assert!(get_caches(&client, "/api/v1/cache").await.is_empty());
+
+    let response = client
+        .get(format!("/api/v1/cache/{}/revisions", id))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn deleted_cache_is_restored_by_owner() {
+    let client = client().await;
+    let id = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 } }),
+    )
+    .await;
+    let response = client
+        .delete(format!("/api/v1/cache/{}", id))
+        .header(auth(ALICE))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let restore = |user| {
+        client
+            .post(format!("/api/v1/cache/{}/restore", id))
+            .header(auth(user))
+    };
+    assert_eq!(restore(BOB).dispatch().await.status(), Status::Forbidden);
+    assert_eq!(restore(ALICE).dispatch().await.status(), Status::Ok);
+    assert_eq!(restore(ALICE).dispatch().await.status(), Status::NotFound);
+
+    let caches = get_caches(&client, &format!("/api/v1/cache/{}", id)).await;
+    assert_eq!(caches[0]["owner_id"], ALICE.1);
+}
+
+#[rocket::async_test]
+async fn history_lists_changes_to_owner_and_moderator() {
+    let client = client().await;
+    let id = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 }, "description": "Old" }),
+    )
+    .await;
+    let response = client
+        .put(format!("/api/v1/cache/{}", id))
+        .header(ContentType::JSON)
+        .header(auth(ALICE))
+        .body(json!({ "name": "Test cache", "position": { "lat": 1.0, "lng": 2.0 }, "description": "New" }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .delete(format!("/api/v1/cache/{}", id))
+        .header(auth(ALICE))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let history = |user| {
+        client
+            .get(format!("/api/v1/cache/{}/history", id))
+            .header(auth(user))
+    };
+    assert_eq!(history(BOB).dispatch().await.status(), Status::Forbidden);
+
+    for user in [ALICE, MODERATOR] {
+        let response = history(user).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().await.unwrap();
+        let entries = body["history"].as_array().unwrap();
+        let actions: Vec<_> = entries.iter().map(|e| e["action"].clone()).collect();
+        assert_eq!(actions, vec!["create", "edit", "delete"]);
+        assert!(entries.iter().all(|e| e["actor_id"] == ALICE.1));
+        assert_eq!(
+            entries[1]["changes"]["description"],
+            json!({ "before": "Old", "after": "New" })
+        );
+    }
+}
+
+#[rocket::async_test]
+async fn only_owner_or_moderator_edits_cache() {
+    let client = client().await;
+    let id = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 }, "description": "Mine" }),
+    )
+    .await;
+    let edit = |user| {
+        client
+            .put(format!("/api/v1/cache/{}", id))
+            .header(ContentType::JSON)
+            .header(auth(user))
+            .body(json!({ "name": "Taken", "position": { "lat": 1.0, "lng": 2.0 } }).to_string())
+    };
+
+    assert_eq!(edit(BOB).dispatch().await.status(), Status::Forbidden);
+    let caches = get_caches(&client, &format!("/api/v1/cache/{}", id)).await;
+    assert_eq!(caches[0]["name"], "Test cache");
+    assert_eq!(caches[0]["description"], "Mine");
+
+    assert_eq!(edit(MODERATOR).dispatch().await.status(), Status::Ok);
+    let caches = get_caches(&client, &format!("/api/v1/cache/{}", id)).await;
+    assert_eq!(caches[0]["name"], "Taken");
+}
+
+#[rocket::async_test]
+async fn only_owner_or_moderator_deletes_cache() {
+    let client = client().await;
+    let id = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 } }),
+    )
+    .await;
+    let delete = |user| {
+        client
+            .delete(format!("/api/v1/cache/{}", id))
+            .header(auth(user))
+    };
+
+    assert_eq!(delete(BOB).dispatch().await.status(), Status::Forbidden);
+    let response = client.get(format!("/api/v1/cache/{}", id)).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+
+    assert_eq!(delete(MODERATOR).dispatch().await.status(), Status::Ok);
+    let response = client.get(format!("/api/v1/cache/{}", id)).dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn search_orders_caches_by_relevance() {
+    let client = client().await;
+    let in_hint = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 1.0 }, "description": "Old tree", "hint": "Near benches" }),
+    )
+    .await;
+    let in_description = create_cache(
+        &client,
+        BOB,
+        json!({ "position": { "lat": 2.0, "lng": 2.0 }, "description": "Under the bench" }),
+    )
+    .await;
+    create_cache(
+        &client,
+        BOB,
+        json!({ "position": { "lat": 3.0, "lng": 3.0 }, "description": "Behind the wall" }),
+    )
+    .await;
+
+    let caches = get_caches(&client, "/api/v1/cache?q=bench").await;
+    assert_eq!(caches.len(), 2);
+    assert_eq!(caches[0]["_id"]["$oid"], in_description.as_str());
+    assert_eq!(caches[1]["_id"]["$oid"], in_hint.as_str());
+    assert!(caches[0]["score"].as_f64() > caches[1]["score"].as_f64());
+
+    let uri = format!("/api/v1/cache?q=bench&user_id={}", ALICE.1);
+    let caches = get_caches(&client, &uri).await;
+    assert_eq!(caches.len(), 1);
+    assert_eq!(caches[0]["_id"]["$oid"], in_hint.as_str());
+}
+
+#[rocket::async_test]
+async fn cache_is_found_by_short_code() {
+    let client = client().await;
+    let response = client
+        .post("/api/v1/cache")
+        .header(ContentType::JSON)
+        .header(auth(ALICE))
+        .body(json!({ "name": "Old oak", "position": { "lat": 1.0, "lng": 2.0 } }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Created);
+    let body: Value = response.into_json().await.unwrap();
+    let code = body["code"].as_str().unwrap().to_string();
+    assert!(code.starts_with("KT"));
+
+    let caches = get_caches(&client, &format!("/api/v1/cache/{}", code.to_lowercase())).await;
+    assert_eq!(caches[0]["_id"], body["id"]);
+    assert_eq!(caches[0]["code"], code.as_str());
+    assert_eq!(caches[0]["name"], "Old oak");
+
+    let response = client
+        .delete(format!("/api/v1/cache/{}", code))
+        .header(auth(ALICE))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .get(format!("/api/v1/cache/{}", code))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+    let response = client.get("/api/v1/cache/KT0000").dispatch().await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn caches_are_fetched_in_batch() {
+    let client = client().await;
+    let first = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 1.0 }, "description": "First" }),
+    )
+    .await;
+    let second = create_cache(
+        &client,
+        BOB,
+        json!({ "position": { "lat": 2.0, "lng": 2.0 }, "description": "Second" }),
+    )
+    .await;
+    let deleted = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 3.0, "lng": 3.0 } }),
+    )
+    .await;
+    client
+        .delete(format!("/api/v1/cache/{}", deleted))
+        .header(auth(ALICE))
+        .dispatch()
+        .await;
+
+    let ids = json!({ "ids": [second, "bad id", first, deleted, "KT0000"] });
+    let response = client
+        .post("/api/v1/cache/batch")
+        .header(ContentType::JSON)
+        .body(ids.to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = response.into_json().await.unwrap();
+    let caches = body["caches"].as_array().unwrap();
+    assert_eq!(caches.len(), 2);
+    assert_eq!(caches[0]["description"], "Second");
+    assert_eq!(caches[1]["description"], "First");
+    assert_eq!(body["invalid"], json!(["bad id"]));
+    assert_eq!(body["not_found"], json!([deleted, "KT0000"]));
+}
+
+#[rocket::async_test]
+async fn bulk_operations_are_checked_per_item() {
+    let client = client().await;
+    let own = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 1.0 } }),
+    )
+    .await;
+    let foreign = create_cache(
+        &client,
+        BOB,
+        json!({ "position": { "lat": 2.0, "lng": 2.0 } }),
+    )
+    .await;
+
+    let operations = json!({ "operations": [
+        { "op": "create", "cache": { "name": "New", "position": { "lat": 3.0, "lng": 3.0 } } },
+        { "op": "update", "id": own, "cache": { "name": "Renamed", "position": { "lat": 1.0, "lng": 1.0 } } },
+        { "op": "delete", "id": foreign },
+        { "op": "delete", "id": "bad id" },
+        { "op": "delete", "id": "KT0000" },
+        { "op": "create", "cache": { "name": "", "position": { "lat": 4.0, "lng": 4.0 } } },
+    ] });
+    let response = client
+        .post("/api/v1/cache/bulk")
+        .header(ContentType::JSON)
+        .header(auth(ALICE))
+        .body(operations.to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = response.into_json().await.unwrap();
+    let statuses: Vec<_> = body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["status"].as_u64().unwrap())
+        .collect();
+    assert_eq!(statuses, [201, 200, 403, 400, 404, 400]);
+    assert!(body["results"][0]["code"]
+        .as_str()
+        .unwrap()
+        .starts_with("KT"));
+
+    let created = body["results"][0]["id"]["$oid"].as_str().unwrap();
+    let caches = get_caches(&client, &format!("/api/v1/cache/{}", created)).await;
+    assert_eq!(caches[0]["owner_id"], ALICE.1);
+    let caches = get_caches(&client, &format!("/api/v1/cache/{}", own)).await;
+    assert_eq!(caches[0]["name"], "Renamed");
+    let caches = get_caches(&client, &format!("/api/v1/cache/{}", foreign)).await;
+    assert_eq!(caches.len(), 1);
+}
+
+#[rocket::async_test]
+async fn repeated_create_with_idempotency_key_is_replayed() {
+    let client = client().await;
+    let cache = json!({ "name": "Once", "position": { "lat": 5.0, "lng": 5.0 } });
+    let create = |body: Value, user| {
+        client
+            .post("/api/v1/cache")
+            .header(ContentType::JSON)
+            .header(auth(user))
+            .header(Header::new("Idempotency-Key", "create-once"))
+            .body(body.to_string())
+            .dispatch()
+    };
+
+    let first = create(cache.clone(), ALICE).await;
+    assert_eq!(first.status(), Status::Created);
+    assert!(first.headers().get_one("Idempotent-Replayed").is_none());
+    let first: Value = first.into_json().await.unwrap();
+
+    let repeated = create(cache.clone(), ALICE).await;
+    assert_eq!(repeated.status(), Status::Created);
+    assert_eq!(
+        repeated.headers().get_one("Idempotent-Replayed"),
+        Some("true")
+    );
+    let repeated: Value = repeated.into_json().await.unwrap();
+    assert_eq!(repeated, first);
+
+    let changed = json!({ "name": "Twice", "position": { "lat": 5.0, "lng": 5.0 } });
+    let response = create(changed, ALICE).await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+
+    // Keys of different users do not clash
+    let response = create(cache, BOB).await;
+    assert_eq!(response.status(), Status::Created);
+    assert!(response.headers().get_one("Idempotent-Replayed").is_none());
+
+    let caches = get_caches(&client, "/api/v1/cache?fields=full").await;
+    let named_once = caches.iter().filter(|c| c["name"] == "Once").count();
+    assert_eq!(named_once, 2);
+}
+
+#[rocket::async_test]
+async fn cache_without_name_is_rejected() {
+    let client = client().await;
+    let response = client
+        .post("/api/v1/cache")
+        .header(ContentType::JSON)
+        .header(auth(ALICE))
+        .body(json!({ "name": "  ", "position": { "lat": 1.0, "lng": 2.0 } }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn malformed_id_is_rejected() {
+    let client = client().await;
+    let response = client.get("/api/v1/cache/not-an-id").dispatch().await;
+    assert_eq!(response.status(), Status::BadRequest);
+}
@@ -0,0 +1,235 @@
+//! Test harness running the service with in-memory storage against
+//! a stub of the login service
+#![allow(dead_code)]
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use msd_cache_service::config::Config;
+use rocket::{
+    figment::Figment,
+    http::{ContentType, Header, Status},
+    local::asynchronous::Client,
+};
+use serde_json::{json, Value};
+
+/// Password accepted by the login stub for every known user
+pub const PASSWORD: &str = "secret";
+
+/// Users known to the login stub: email and id
+pub const ALICE: (&str, i32) = ("alice@example.com", 1);
+pub const BOB: (&str, i32) = ("bob@example.com", 2);
+pub const MODERATOR: (&str, i32) = ("moderator@example.com", 100);
+
+const USERS: &[(&str, i32)] = &[ALICE, BOB, MODERATOR];
+
+/// Logins for which the login stub fails: answers garbage or server error
+pub const GARBLED: &str = "garbled@example.com";
+pub const BROKEN: &str = "broken@example.com";
+
+/// Request received by stub server
+#[derive(Debug, Clone)]
+pub struct StubRequest {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercase
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl StubRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Starts HTTP server on random local port and returns its base URL.
+/// `handler` returns status line like `200 OK` and JSON body
+pub fn start_stub<F>(handler: F) -> String
+where
+    F: Fn(&StubRequest) -> (&'static str, String) + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind stub server");
+    let address = format!("http://{}/", listener.local_addr().unwrap());
+    let handler = Arc::new(handler);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handler = handler.clone();
+            thread::spawn(move || serve(stream, handler.as_ref()));
+        }
+    });
+
+    address
+}
+
+fn serve(mut stream: TcpStream, handler: &dyn Fn(&StubRequest) -> (&'static str, String)) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let content_length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; content_length];
+    let _ = reader.read_exact(&mut body);
+
+    let request = StubRequest {
+        method,
+        path,
+        headers,
+        body,
+    };
+    let (status, body) = handler(&request);
+
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+}
+
+/// Login service stub. It answers `POST /api/v1/user/login` with user id
+/// for known credentials and 401 otherwise. Any `GET` is answered with 200
+/// for readiness checks
+pub fn start_login_stub() -> String {
+    start_stub(login_stub)
+}
+
+/// Same as `start_login_stub`, also returns count of received login requests
+pub fn start_counted_login_stub() -> (String, Arc<AtomicUsize>) {
+    let logins = Arc::new(AtomicUsize::new(0));
+    let count = logins.clone();
+    let address = start_stub(move |request| {
+        if request.path == "/api/v1/user/login" {
+            count.fetch_add(1, Ordering::SeqCst);
+        }
+        login_stub(request)
+    });
+    (address, logins)
+}
+
+fn login_stub(request: &StubRequest) -> (&'static str, String) {
+    let is_login = request.method == "POST" && request.path == "/api/v1/user/login";
+    if !is_login {
+        return ("200 OK", "{}".to_string());
+    }
+
+    let credentials: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+    if credentials["email"] == GARBLED {
+        return ("200 OK", "<html>".to_string());
+    }
+    if credentials["email"] == BROKEN {
+        return ("500 Internal Server Error", "{}".to_string());
+    }
+    let user = USERS
+        .iter()
+        .find(|(email, _)| credentials["email"] == *email && credentials["password"] == PASSWORD);
+    match user {
+        Some((_, id)) => ("200 OK", json!({ "id": id }).to_string()),
+        None => ("401 Unauthorized", "{}".to_string()),
+    }
+}
+
+/// Starts server accepting any request and returns its URL with received requests
+pub fn start_receiver() -> (String, Arc<Mutex<Vec<StubRequest>>>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+    let address = start_stub(move |request| {
+        log.lock().unwrap().push(request.clone());
+        ("200 OK", "{}".to_string())
+    });
+    (address, received)
+}
+
+/// Creates client of the service using in-memory storage and login stub.
+/// `MODERATOR` is configured as moderator
+pub async fn client() -> Client {
+    client_with(|figment| figment).await
+}
+
+/// Same as `client` with settings changed by `configure`
+pub async fn client_with(configure: impl FnOnce(Figment) -> Figment) -> Client {
+    let figment = Figment::from(rocket::Config::debug_default())
+        .merge(("log_level", "off"))
+        .merge(("storage_backend", "memory"))
+        .merge(("login_service_address", start_login_stub()))
+        .merge(("moderator_ids", vec![MODERATOR.1]));
+    let figment = configure(figment);
+    let config = Config::load(&figment).expect("Invalid test configuration");
+
+    let rocket = msd_cache_service::build(figment, config).await;
+    Client::tracked(rocket)
+        .await
+        .expect("Failed to launch service")
+}
+
+/// Basic authorization header for user known to login stub
+pub fn auth((email, _): (&str, i32)) -> Header<'static> {
+    basic(email, PASSWORD)
+}
+
+pub fn basic(email: &str, password: &str) -> Header<'static> {
+    let credentials = base64::encode(format!("{}:{}", email, password));
+    Header::new("Authorization", format!("Basic {}", credentials))
+}
+
+/// Creates cache as `user` and returns its id. Cache is named `Test cache` unless name given
+pub async fn create_cache(client: &Client, user: (&str, i32), mut cache: Value) -> String {
+    if cache.get("name").is_none() {
+        cache["name"] = "Test cache".into();
+    }
+
+    let response = client
+        .post("/api/v1/cache")
+        .header(ContentType::JSON)
+        .header(auth(user))
+        .body(cache.to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Created);
+
+    let body: Value = response.into_json().await.unwrap();
+    body["id"]["$oid"].as_str().unwrap().to_string()
+}
+
+/// Returns caches from successful `GET` request to `uri`
+pub async fn get_caches(client: &Client, uri: &str) -> Vec<Value> {
+    let response = client.get(uri).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = response.into_json().await.unwrap();
+    body["caches"].as_array().unwrap().clone()
+}
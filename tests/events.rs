@@ -0,0 +1,94 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{auth, client, create_cache, ALICE};
+use rocket::{
+    http::{Header, Status},
+    local::asynchronous::LocalResponse,
+    tokio::{io::AsyncReadExt, time::timeout},
+};
+use serde_json::json;
+
+/// Reads stream until it contains `count` complete events
+async fn read_events(response: &mut LocalResponse<'_>, count: usize) -> String {
+    let mut received = String::new();
+    let mut buffer = [0; 1024];
+
+    while received.matches("\n\n").count() < count {
+        let read = timeout(Duration::from_secs(5), response.read(&mut buffer))
+            .await
+            .expect("Event was not received in time")
+            .unwrap();
+        assert!(read > 0, "Stream ended");
+        received.push_str(std::str::from_utf8(&buffer[..read]).unwrap());
+    }
+    received
+}
+
+#[rocket::async_test]
+async fn missed_events_in_area_are_replayed() {
+    let client = client().await;
+    let inside = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 10.0, "lng": 10.0 } }),
+    )
+    .await;
+    let outside = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 50.0, "lng": 50.0 } }),
+    )
+    .await;
+    let deleted = client
+        .delete(format!("/api/v1/cache/{}", inside))
+        .header(auth(ALICE))
+        .dispatch()
+        .await;
+    assert_eq!(deleted.status(), Status::Ok);
+
+    let mut response = client
+        .get("/api/v1/cache/events?min_lat=0&max_lat=20&min_long=0&max_long=20")
+        .header(Header::new("Last-Event-ID", "0"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let received = read_events(&mut response, 2).await;
+    let events: Vec<_> = received.trim_end().split("\n\n").collect();
+    assert!(events[0].contains("event:created"));
+    assert!(events[0].contains("id:1"));
+    assert!(events[0].contains(&inside));
+    assert!(events[1].contains("event:deleted"));
+    assert!(events[1].contains("id:3"));
+    assert!(!received.contains(&outside));
+}
+
+#[rocket::async_test]
+async fn new_events_are_streamed() {
+    let client = client().await;
+    let mut response = client.get("/api/v1/cache/events").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let id = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 } }),
+    )
+    .await;
+
+    let received = read_events(&mut response, 1).await;
+    assert!(received.contains("event:created"));
+    assert!(received.contains(&id));
+}
+
+#[rocket::async_test]
+async fn incomplete_area_is_rejected() {
+    let client = client().await;
+    let response = client
+        .get("/api/v1/cache/events?min_lat=0&max_lat=20")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+}
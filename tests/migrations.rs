@@ -0,0 +1,33 @@
+//! Data migrations. They need running MongoDB, so tests are ignored unless
+//! run with `--ignored` and `MONGODB_TEST_URL`
+
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    Client,
+};
+use msd_cache_service::db::run_migrations;
+
+#[rocket::async_test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URL"]
+async fn language_is_set_from_name_too() {
+    let url = std::env::var("MONGODB_TEST_URL")
+        .expect("MONGODB_TEST_URL must be set to run MongoDB tests");
+    let database = Client::with_uri_str(&url)
+        .await
+        .expect("Invalid MONGODB_TEST_URL")
+        .database(&format!("msd_cache_test_{}", ObjectId::new()));
+    let collection = database.collection::<Document>("cache");
+    collection
+        .insert_one(
+            doc! { "name": "Старый дуб", "description": "Under the oak", "hint": "roots" },
+            None,
+        )
+        .await
+        .unwrap();
+
+    run_migrations(&database, false).await.unwrap();
+    let cache = collection.find_one(None, None).await.unwrap().unwrap();
+    database.drop(None).await.unwrap();
+
+    assert_eq!(cache.get_str("language").unwrap(), "russian");
+}
@@ -0,0 +1,244 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use common::{auth, client, client_with, create_cache, start_counted_login_stub, ALICE, BOB};
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::asynchronous::Client,
+};
+use serde_json::{json, Value};
+
+#[rocket::async_test]
+async fn request_id_is_echoed() {
+    let client = client().await;
+    let response = client
+        .get("/api/v1/cache")
+        .header(Header::new("X-Request-Id", "client-id_1.2"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("X-Request-Id"),
+        Some("client-id_1.2")
+    );
+}
+
+#[rocket::async_test]
+async fn unsafe_request_id_is_replaced() {
+    let client = client().await;
+    let long = "a".repeat(129);
+    for id in ["with space", "line\u{7f}break", "<script>", long.as_str()] {
+        let response = client
+            .get("/api/v1/cache")
+            .header(Header::new("X-Request-Id", id.to_string()))
+            .dispatch()
+            .await;
+        let echoed = response.headers().get_one("X-Request-Id").unwrap();
+        assert_ne!(echoed, id);
+        assert!(!echoed.is_empty());
+    }
+}
+
+#[rocket::async_test]
+async fn health_does_not_check_dependencies() {
+    let client =
+        client_with(|figment| figment.merge(("login_service_address", "http://127.0.0.1:1/")))
+            .await;
+    let response = client.get("/health").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let body: Value = response.into_json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+}
+
+#[rocket::async_test]
+async fn ready_reports_dependencies() {
+    let client = client().await;
+    let response = client.get("/ready").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let body: Value = response.into_json().await.unwrap();
+    assert_eq!(body["ready"], true);
+    for dependency in ["database", "database_setup", "login_service"] {
+        assert_eq!(body[dependency]["ready"], true, "{}", dependency);
+    }
+}
+
+#[rocket::async_test]
+async fn ready_fails_without_login_service() {
+    let client =
+        client_with(|figment| figment.merge(("login_service_address", "http://127.0.0.1:1/")))
+            .await;
+    let response = client.get("/ready").dispatch().await;
+    assert_eq!(response.status(), Status::ServiceUnavailable);
+    let body: Value = response.into_json().await.unwrap();
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["database"]["ready"], true);
+    assert_eq!(body["login_service"]["ready"], false);
+    assert_eq!(body["login_service"]["error"], "unreachable");
+}
+
+#[rocket::async_test]
+async fn metrics_count_requests_by_route() {
+    let client = client().await;
+    create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 } }),
+    )
+    .await;
+
+    let response = client.get("/metrics").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let metrics = response.into_string().await.unwrap();
+    let created = metrics.lines().any(|line| {
+        line.starts_with("http_requests_total{")
+            && line.contains(r#"method="POST""#)
+            && line.contains(r#"route="/api/v1/cache""#)
+            && line.contains(r#"status="201""#)
+    });
+    assert!(created, "{}", metrics);
+    assert!(metrics.contains(r#"login_service_calls_total{outcome="success"}"#));
+}
+
+#[rocket::async_test]
+async fn preflight_is_answered_for_served_routes() {
+    let client = client().await;
+    let response = client
+        .options("/api/v1/cache/0123456789abcdef01234567")
+        .header(Header::new("Origin", "https://map.example"))
+        .header(Header::new("Access-Control-Request-Method", "PUT"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NoContent);
+    let headers = response.headers();
+    assert_eq!(headers.get_one("Access-Control-Allow-Origin"), Some("*"));
+    assert!(headers
+        .get_one("Access-Control-Allow-Methods")
+        .unwrap()
+        .contains("PUT"));
+    assert!(headers
+        .get_one("Access-Control-Allow-Headers")
+        .unwrap()
+        .contains("Idempotency-Key"));
+    assert_eq!(headers.get_one("Access-Control-Max-Age"), Some("86400"));
+
+    let response = client
+        .options("/api/v1/unknown")
+        .header(Header::new("Origin", "https://map.example"))
+        .header(Header::new("Access-Control-Request-Method", "GET"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn only_allowed_origins_get_cors_headers() {
+    let client =
+        client_with(|figment| figment.merge(("cors_allowed_origins", vec!["https://map.example"])))
+            .await;
+
+    let response = client
+        .get("/api/v1/cache")
+        .header(Header::new("Origin", "https://map.example"))
+        .dispatch()
+        .await;
+    let headers = response.headers();
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Origin"),
+        Some("https://map.example")
+    );
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Credentials"),
+        Some("true")
+    );
+    assert_eq!(headers.get_one("Vary"), Some("Origin"));
+
+    let response = client
+        .get("/api/v1/cache")
+        .header(Header::new("Origin", "https://evil.example"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response
+        .headers()
+        .get_one("Access-Control-Allow-Origin")
+        .is_none());
+}
+
+/// Client allowing two reads and one write each minute
+async fn limited_client() -> Client {
+    client_with(|figment| {
+        figment
+            .merge(("rate_limit_reads_per_minute", 2))
+            .merge(("rate_limit_writes_per_minute", 1))
+    })
+    .await
+}
+
+#[rocket::async_test]
+async fn reads_are_limited() {
+    let client = limited_client().await;
+    for _ in 0..2 {
+        let response = client.get("/api/v1/cache").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    let response = client.get("/api/v1/cache").dispatch().await;
+    assert_eq!(response.status(), Status::TooManyRequests);
+    assert_eq!(response.headers().get_one("Retry-After"), Some("30"));
+}
+
+#[rocket::async_test]
+async fn writes_are_limited_per_user() {
+    let client = limited_client().await;
+    let create = |user| {
+        client
+            .post("/api/v1/cache")
+            .header(ContentType::JSON)
+            .header(auth(user))
+            .body(
+                json!({ "name": "Test cache", "position": { "lat": 1.0, "lng": 2.0 } }).to_string(),
+            )
+    };
+
+    assert_eq!(create(ALICE).dispatch().await.status(), Status::Created);
+    let response = create(ALICE).dispatch().await;
+    assert_eq!(response.status(), Status::TooManyRequests);
+    assert!(response.headers().get_one("Retry-After").is_some());
+    assert_eq!(create(BOB).dispatch().await.status(), Status::Created);
+
+    // Writes have own budget
+    let response = client.get("/api/v1/cache").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[rocket::async_test]
+async fn writes_over_budget_do_not_reach_login_service() {
+    let (login_service, logins) = start_counted_login_stub();
+    let client = client_with(|figment| {
+        figment
+            .merge(("rate_limit_writes_per_minute", 1))
+            .merge(("login_service_address", login_service))
+    })
+    .await;
+    let create = |user| {
+        client
+            .post("/api/v1/cache")
+            .header(ContentType::JSON)
+            .header(auth(user))
+            .body(
+                json!({ "name": "Test cache", "position": { "lat": 1.0, "lng": 2.0 } }).to_string(),
+            )
+    };
+
+    assert_eq!(create(ALICE).dispatch().await.status(), Status::Created);
+    for _ in 0..3 {
+        let response = create(ALICE).dispatch().await;
+        assert_eq!(response.status(), Status::TooManyRequests);
+        assert!(response.headers().get_one("Retry-After").is_some());
+    }
+    assert_eq!(logins.load(Ordering::SeqCst), 1);
+
+    assert_eq!(create(BOB).dispatch().await.status(), Status::Created);
+    assert_eq!(logins.load(Ordering::SeqCst), 2);
+}
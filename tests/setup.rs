@@ -0,0 +1,62 @@
+//! Launch with MongoDB storage. Tests needing running MongoDB are ignored
+//! unless run with `--ignored` and `MONGODB_TEST_URL`, see tests/store.rs
+
+mod common;
+
+use common::start_login_stub;
+use mongodb::bson::{doc, oid::ObjectId};
+use msd_cache_service::config::Config;
+use rocket::{figment::Figment, http::Status, local::asynchronous::Client, Build, Rocket};
+use serde_json::Value;
+
+/// Service using MongoDB at `url` and database `name`
+async fn service(url: &str, name: &str) -> Rocket<Build> {
+    let figment = Figment::from(rocket::Config::debug_default())
+        .merge(("log_level", "off"))
+        .merge(("storage_backend", "mongodb"))
+        .merge(("database_url", url))
+        .merge(("database_name", name))
+        .merge(("run_migrations", false))
+        .merge(("login_service_address", start_login_stub()));
+    let config = Config::load(&figment).expect("Invalid test configuration");
+    msd_cache_service::build(figment, config).await
+}
+
+#[rocket::async_test]
+async fn unreachable_database_is_set_up_in_background() {
+    let rocket = service(
+        "mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=100",
+        "msd_cache_test",
+    )
+    .await;
+    let client = Client::tracked(rocket)
+        .await
+        .expect("Service must start without database");
+
+    let response = client.get("/ready").dispatch().await;
+    assert_eq!(response.status(), Status::ServiceUnavailable);
+    let body: Value = response.into_json().await.unwrap();
+    assert_eq!(body["database_setup"]["ready"], false);
+    assert_eq!(body["database_setup"]["error"], "failed");
+}
+
+#[rocket::async_test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URL"]
+async fn launch_fails_on_repeated_codes() {
+    let url = std::env::var("MONGODB_TEST_URL")
+        .expect("MONGODB_TEST_URL must be set to run MongoDB tests");
+    let name = format!("msd_cache_test_{}", ObjectId::new());
+    let database = mongodb::Client::with_uri_str(&url)
+        .await
+        .expect("Invalid MONGODB_TEST_URL")
+        .database(&name);
+    database
+        .collection("cache")
+        .insert_many([doc! { "code": "KT1A2B" }, doc! { "code": "KT1A2B" }], None)
+        .await
+        .unwrap();
+
+    let launched = service(&url, &name).await.ignite().await;
+    database.drop(None).await.unwrap();
+    assert!(launched.is_err(), "unique code index must not be built");
+}
@@ -0,0 +1,188 @@
+//! Storage checks run against in-memory storage. MongoDB versions of them
+//! are ignored unless run with `--ignored` and `MONGODB_TEST_URL`, like
+//! `mongodb://localhost:27017`. Each MongoDB test uses new database,
+//! dropped after it passes
+
+use mongodb::{
+    bson::{oid::ObjectId, DateTime},
+    Client, Database,
+};
+use msd_cache_service::db::{
+    Cache, CacheStore, IdempotencyRecord, IdempotencyStore, LatLong, MemoryStore, MongoStore,
+    StoredResponse,
+};
+
+trait Store: CacheStore + IdempotencyStore {}
+impl<S: CacheStore + IdempotencyStore> Store for S {}
+
+/// New database of MongoDB given by `MONGODB_TEST_URL`
+async fn mongo_database() -> (Client, Database) {
+    let url = std::env::var("MONGODB_TEST_URL")
+        .expect("MONGODB_TEST_URL must be set to run MongoDB tests");
+    let client = Client::with_uri_str(&url)
+        .await
+        .expect("Invalid MONGODB_TEST_URL");
+    let database = client.database(&format!("msd_cache_test_{}", ObjectId::new()));
+    (client, database)
+}
+
+/// Defines test of each check for every storage
+macro_rules! store_tests {
+    ($($check:ident),* $(,)?) => {
+        mod memory {
+            use super::*;
+            $(
+                #[rocket::async_test]
+                async fn $check() {
+                    super::$check(&MemoryStore::new()).await;
+                }
+            )*
+        }
+
+        mod mongo {
+            use super::*;
+            $(
+                #[rocket::async_test]
+                #[ignore = "needs MongoDB at MONGODB_TEST_URL"]
+                async fn $check() {
+                    let (client, database) = mongo_database().await;
+                    super::$check(&MongoStore::new(client, database.clone())).await;
+                    database.drop(None).await.unwrap();
+                }
+            )*
+        }
+    };
+}
+
+store_tests!(
+    purge_removes_deleted_caches_with_revisions,
+    restored_cache_is_not_purged,
+    moved_cache_is_deleted_from_old_area,
+    key_of_interrupted_request_is_reclaimed,
+    completed_key_is_kept_until_ttl,
+);
+
+fn cache(name: &str) -> Cache {
+    Cache {
+        id: None,
+        code: None,
+        name: name.to_string(),
+        position: LatLong { lat: 1.0, lng: 1.0 },
+        description: None,
+        hint: None,
+        owner_id: Some(1),
+        deleted_at: None,
+        created_at: Some(DateTime::now()),
+        updated_at: Some(DateTime::now()),
+    }
+}
+
+/// Time after all deletions made so far
+fn later() -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() + 1000)
+}
+
+fn reservation(expires_at: DateTime) -> IdempotencyRecord {
+    IdempotencyRecord {
+        id: "1:key".to_string(),
+        fingerprint: "fingerprint".to_string(),
+        response: None,
+        expires_at,
+    }
+}
+
+async fn purge_removes_deleted_caches_with_revisions(store: &impl Store) {
+    let kept = store.insert_cache(cache("Kept")).await.unwrap();
+    let removed = store.insert_cache(cache("Removed")).await.unwrap();
+    let id = removed.id.unwrap();
+    store.update_cache(removed).await.unwrap();
+    store.delete_cache_by_id(id).await.unwrap();
+
+    assert_eq!(store.purge_deleted(later()).await.unwrap(), 1);
+    assert!(store.get_any_cache_by_id(id).await.unwrap().is_none());
+    assert!(store.get_revisions(id).await.unwrap().is_empty());
+    assert!(store
+        .get_cache_by_id(kept.id.unwrap())
+        .await
+        .unwrap()
+        .is_some());
+
+    let since = DateTime::from_millis(0);
+    let changes = store.get_changes(Some(since), None).await.unwrap();
+    assert!(changes.iter().any(|c| c.id == id && c.deleted));
+}
+
+async fn restored_cache_is_not_purged(store: &impl Store) {
+    let restored = store.insert_cache(cache("Restored")).await.unwrap();
+    let id = restored.id.unwrap();
+    store.update_cache(restored).await.unwrap();
+    store.delete_cache_by_id(id).await.unwrap();
+    store.restore_cache_by_id(id).await.unwrap();
+
+    assert_eq!(store.purge_deleted(later()).await.unwrap(), 0);
+    assert!(store.get_cache_by_id(id).await.unwrap().is_some());
+    assert_eq!(store.get_revisions(id).await.unwrap().len(), 1);
+}
+
+async fn key_of_interrupted_request_is_reclaimed(store: &impl Store) {
+    let earlier = DateTime::from_millis(DateTime::now().timestamp_millis() - 1000);
+    assert!(store
+        .reserve_key(reservation(earlier))
+        .await
+        .unwrap()
+        .is_none());
+
+    assert!(store
+        .reserve_key(reservation(later()))
+        .await
+        .unwrap()
+        .is_none());
+    let existing = store.reserve_key(reservation(later())).await.unwrap();
+    assert!(existing.unwrap().response.is_none());
+}
+
+async fn completed_key_is_kept_until_ttl(store: &impl Store) {
+    let lease = DateTime::from_millis(DateTime::now().timestamp_millis() + 50);
+    assert!(store
+        .reserve_key(reservation(lease))
+        .await
+        .unwrap()
+        .is_none());
+    let response = StoredResponse {
+        status: 201,
+        body: "{}".to_string(),
+    };
+    store
+        .complete_key("1:key", response, later())
+        .await
+        .unwrap();
+
+    rocket::tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let existing = store.reserve_key(reservation(later())).await.unwrap();
+    assert_eq!(existing.unwrap().response.unwrap().status, 201);
+}
+
+async fn moved_cache_is_deleted_from_old_area(store: &impl Store) {
+    let moved = store.insert_cache(cache("Moved")).await.unwrap();
+    let id = moved.id.unwrap();
+    let since = DateTime::from_millis(DateTime::now().timestamp_millis() - 1);
+    let mut update = moved.clone();
+    update.position = LatLong { lat: 9.0, lng: 9.0 };
+    store.update_cache(update).await.unwrap();
+
+    let area = |lat: f64| {
+        Some((
+            LatLong { lat, lng: lat },
+            LatLong {
+                lat: lat + 1.0,
+                lng: lat + 1.0,
+            },
+        ))
+    };
+    let old_area = store.get_changes(Some(since), area(0.5)).await.unwrap();
+    assert!(old_area.iter().all(|c| c.id == id && c.deleted));
+    assert_eq!(old_area.len(), 1);
+    let new_area = store.get_changes(Some(since), area(8.5)).await.unwrap();
+    assert!(new_area.iter().all(|c| c.id == id && !c.deleted));
+    assert_eq!(new_area.len(), 1);
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::{auth, client, create_cache, ALICE};
+use rocket::{
+    http::{ContentType, Status},
+    local::asynchronous::Client,
+};
+use serde_json::{json, Value};
+
+const AREA: &str = "bbox=0,0,20,20";
+
+async fn changes(client: &Client, query: &str) -> Value {
+    let response = client
+        .get(format!("/api/v1/cache/changes?{}", query))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    response.into_json().await.unwrap()
+}
+
+fn ids(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|id| id["$oid"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[rocket::async_test]
+async fn changes_since_token_are_returned() {
+    let client = client().await;
+    let removed = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 1.0 } }),
+    )
+    .await;
+    let edited = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 2.0, "lng": 2.0 } }),
+    )
+    .await;
+    let outside = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 50.0, "lng": 50.0 } }),
+    )
+    .await;
+
+    let initial = changes(&client, AREA).await;
+    let mut upserted = ids(&initial["upserted"]);
+    upserted.sort();
+    let mut expected = vec![removed.clone(), edited.clone()];
+    expected.sort();
+    assert_eq!(upserted, expected);
+    assert!(ids(&initial["deleted"]).is_empty());
+
+    let response = client
+        .delete(format!("/api/v1/cache/{}", removed))
+        .header(auth(ALICE))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let response = client
+        .put(format!("/api/v1/cache/{}", edited))
+        .header(ContentType::JSON)
+        .header(auth(ALICE))
+        .body(json!({ "name": "Moved", "position": { "lat": 3.0, "lng": 3.0 } }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let token = initial["token"].as_str().unwrap();
+    let delta = changes(&client, &format!("since={}&{}", token, AREA)).await;
+    assert_eq!(ids(&delta["deleted"]), vec![removed]);
+    assert_eq!(ids(&delta["upserted"]), vec![edited]);
+    assert!(!delta.to_string().contains(&outside));
+}
+
+#[rocket::async_test]
+async fn invalid_token_is_rejected() {
+    let client = client().await;
+    let response = client
+        .get("/api/v1/cache/changes?since=yesterday")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn expired_token_requires_full_sync() {
+    let client = client().await;
+    let response = client.get("/api/v1/cache/changes?since=0").dispatch().await;
+    assert_eq!(response.status(), Status::Gone);
+}
+
+#[rocket::async_test]
+async fn cache_moved_out_of_area_is_deleted() {
+    let client = client().await;
+    let left = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 1.0 } }),
+    )
+    .await;
+    let stayed = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 2.0, "lng": 2.0 } }),
+    )
+    .await;
+    let initial = changes(&client, AREA).await;
+
+    for (id, position) in [
+        (&left, json!({ "lat": 50.0, "lng": 50.0 })),
+        (&stayed, json!({ "lat": 3.0, "lng": 3.0 })),
+    ] {
+        let response = client
+            .put(format!("/api/v1/cache/{}", id))
+            .header(ContentType::JSON)
+            .header(auth(ALICE))
+            .body(json!({ "name": "Moved", "position": position }).to_string())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    let token = initial["token"].as_str().unwrap();
+    let delta = changes(&client, &format!("since={}&{}", token, AREA)).await;
+    assert_eq!(ids(&delta["deleted"]), vec![left.clone()]);
+    assert_eq!(ids(&delta["upserted"]), vec![stayed]);
+
+    let delta = changes(&client, &format!("since={}&bbox=40,40,60,60", token)).await;
+    assert_eq!(ids(&delta["upserted"]), vec![left]);
+    assert!(ids(&delta["deleted"]).is_empty());
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{auth, client, create_cache, start_receiver, ALICE, MODERATOR};
+use msd_cache_service::webhooks::{sign, SIGNATURE_HEADER};
+use rocket::{
+    http::{ContentType, Status},
+    tokio::time::sleep,
+};
+use serde_json::{json, Value};
+
+#[rocket::async_test]
+async fn subscribed_events_are_delivered_signed() {
+    let client = client().await;
+    let (url, received) = start_receiver();
+
+    let response = client
+        .post("/api/v1/webhooks")
+        .header(ContentType::JSON)
+        .header(auth(MODERATOR))
+        .body(json!({ "url": url, "secret": "hook-secret", "events": ["created"] }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Created);
+    let body: Value = response.into_json().await.unwrap();
+    let webhook_id = body["id"]["$oid"].as_str().unwrap().to_string();
+
+    let cache_id = create_cache(
+        &client,
+        ALICE,
+        json!({ "position": { "lat": 1.0, "lng": 2.0 } }),
+    )
+    .await;
+
+    let mut requests = Vec::new();
+    for _ in 0..50 {
+        requests = received.lock().unwrap().clone();
+        if !requests.is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(requests.len(), 1);
+
+    let request = &requests[0];
+    let payload = String::from_utf8(request.body.clone()).unwrap();
+    assert_eq!(
+        request.header(SIGNATURE_HEADER),
+        Some(sign("hook-secret", &payload).as_str())
+    );
+    let payload: Value = serde_json::from_str(&payload).unwrap();
+    assert_eq!(payload["type"], "created");
+    assert_eq!(payload["cache_id"], cache_id.as_str());
+    let event_id = payload["id"].as_str().unwrap();
+    assert_eq!(event_id.len(), 24);
+    assert!(payload["timestamp"].as_str().is_some());
+
+    // Delivery is recorded after endpoint answers
+    let mut deliveries = Value::Null;
+    for _ in 0..50 {
+        let response = client
+            .get(format!("/api/v1/webhooks/{}/deliveries", webhook_id))
+            .header(auth(MODERATOR))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().await.unwrap();
+        deliveries = body["deliveries"].clone();
+        if !deliveries.as_array().unwrap().is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(deliveries[0]["success"], true);
+    assert_eq!(deliveries[0]["attempt"], 1);
+    assert_eq!(deliveries[0]["event_id"]["$oid"], event_id);
+}
+
+#[rocket::async_test]
+async fn secret_is_not_shown() {
+    let client = client().await;
+    let response = client
+        .post("/api/v1/webhooks")
+        .header(ContentType::JSON)
+        .header(auth(MODERATOR))
+        .body(
+            json!({ "url": "http://127.0.0.1:9/", "secret": "hook-secret", "events": ["deleted"] })
+                .to_string(),
+        )
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Created);
+
+    let response = client
+        .get("/api/v1/webhooks")
+        .header(auth(MODERATOR))
+        .dispatch()
+        .await;
+    let body = response.into_string().await.unwrap();
+    assert!(body.contains("http://127.0.0.1:9/"));
+    assert!(!body.contains("hook-secret"));
+}
+
+#[rocket::async_test]
+async fn only_moderators_manage_webhooks() {
+    let client = client().await;
+    let response = client
+        .get("/api/v1/webhooks")
+        .header(auth(ALICE))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+}